@@ -0,0 +1,248 @@
+use bevy::{
+    input::{
+        mouse::{MouseMotion, MouseScrollUnit, MouseWheel},
+        touch::Touches,
+    },
+    prelude::*,
+    transform::TransformSystem,
+    window::PrimaryWindow,
+};
+use bevy_egui::EguiContexts;
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<FitMazeEvent>()
+            .add_systems(Startup, setup)
+            .add_systems(Update, (fit_on_key, pan, touch_gestures, zoom).chain())
+            .add_systems(
+                PostUpdate,
+                (
+                    fit_maze_to_view
+                        .run_if(resource_exists::<MazeBounds>)
+                        .run_if(on_event::<FitMazeEvent>),
+                    clamp_to_maze.run_if(resource_exists::<MazeBounds>),
+                )
+                    .chain()
+                    .before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+/// Centers the camera on the maze and zooms so that the whole maze is visible.
+#[derive(Event, Default)]
+pub struct FitMazeEvent;
+
+/// World space rectangle of the maze currently shown, used to fit and clamp the view.
+#[derive(Resource, Deref)]
+pub struct MazeBounds(pub Rect);
+
+#[derive(Component)]
+struct CameraController {
+    target_scale: f32,
+    /// Screen offset from the window center that stays fixed while zooming.
+    zoom_anchor: Option<Vec2>,
+    max_scale: f32,
+}
+
+const MIN_SCALE: f32 = 0.05;
+const ZOOM_STEP: f32 = 1.2;
+const ZOOM_SMOOTHING: f32 = 15.0;
+const PIXELS_PER_SCROLL_LINE: f32 = 100.0;
+const FIT_MARGIN: f32 = 1.1;
+const MAX_ZOOM_OUT: f32 = 4.0;
+
+fn setup(mut commands: Commands) {
+    commands.spawn((
+        Camera2d,
+        CameraController {
+            target_scale: 1.0,
+            zoom_anchor: None,
+            max_scale: 1.0,
+        },
+    ));
+}
+
+fn fit_on_key(
+    mut egui: EguiContexts,
+    input: Res<ButtonInput<KeyCode>>,
+    mut fit_event: EventWriter<FitMazeEvent>,
+) {
+    // Typing an F in a text field doesn't fit the view
+    if input.just_pressed(KeyCode::KeyF) && !egui.ctx_mut().wants_keyboard_input() {
+        fit_event.send_default();
+    }
+}
+
+fn pan(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    camera: Single<(&mut Transform, &OrthographicProjection), With<CameraController>>,
+    mut mouse_motion_event: EventReader<MouseMotion>,
+) {
+    let (mut transform, projection) = camera.into_inner();
+
+    if mouse_input.pressed(MouseButton::Middle) {
+        for mouse_motion in mouse_motion_event.read() {
+            let mut delta = mouse_motion.delta;
+            delta.x *= -1.0;
+
+            delta *= projection.scale;
+
+            transform.translation += Vec3::from((delta, 0.0));
+        }
+    }
+}
+
+fn zoom(
+    time: Res<Time>,
+    mut egui: EguiContexts,
+    window: Single<&Window, With<PrimaryWindow>>,
+    mut mouse_wheel: EventReader<MouseWheel>,
+    camera: Single<(
+        &mut Transform,
+        &mut OrthographicProjection,
+        &mut CameraController,
+    )>,
+) {
+    let (mut transform, mut projection, mut controller) = camera.into_inner();
+
+    let pointer_over_ui = egui.ctx_mut().is_pointer_over_area();
+
+    for mouse_wheel in mouse_wheel.read() {
+        if pointer_over_ui {
+            continue;
+        }
+
+        let lines = match mouse_wheel.unit {
+            MouseScrollUnit::Line => mouse_wheel.y,
+            MouseScrollUnit::Pixel => mouse_wheel.y / PIXELS_PER_SCROLL_LINE,
+        };
+
+        controller.target_scale = (controller.target_scale * ZOOM_STEP.powf(-lines))
+            .clamp(MIN_SCALE, controller.max_scale.max(MIN_SCALE));
+        controller.zoom_anchor = window
+            .cursor_position()
+            .map(|cursor| screen_offset(&window, cursor));
+    }
+
+    let scale = projection.scale;
+    if scale == controller.target_scale {
+        return;
+    }
+
+    let t = 1.0 - f32::exp(-ZOOM_SMOOTHING * time.delta_secs());
+    let mut new_scale = scale + (controller.target_scale - scale) * t;
+    if (controller.target_scale - new_scale).abs() < controller.target_scale * 1e-3 {
+        new_scale = controller.target_scale;
+    }
+
+    if let Some(anchor) = controller.zoom_anchor {
+        transform.translation += Vec3::from((anchor * (scale - new_scale), 0.0));
+    }
+
+    projection.scale = new_scale;
+
+    if new_scale == controller.target_scale {
+        controller.zoom_anchor = None;
+    }
+}
+
+fn touch_gestures(
+    touches: Res<Touches>,
+    mut egui: EguiContexts,
+    window: Single<&Window, With<PrimaryWindow>>,
+    camera: Single<(
+        &mut Transform,
+        &mut OrthographicProjection,
+        &mut CameraController,
+    )>,
+) {
+    if egui.ctx_mut().wants_pointer_input() {
+        return;
+    }
+
+    let (mut transform, mut projection, mut controller) = camera.into_inner();
+    let active_touches = touches.iter().collect::<Vec<_>>();
+
+    match active_touches.as_slice() {
+        [] => {}
+
+        // One finger drags the maze around
+        [touch] => {
+            let delta = touch.delta();
+            transform.translation += Vec3::new(-delta.x, delta.y, 0.0) * projection.scale;
+        }
+
+        // Two fingers pinch to zoom about their midpoint and drag with it
+        [first, second, ..] => {
+            let previous_dist = first
+                .previous_position()
+                .distance(second.previous_position());
+            let dist = first.position().distance(second.position());
+            if previous_dist <= 0.0 || dist <= 0.0 {
+                return;
+            }
+
+            let previous_mid = (first.previous_position() + second.previous_position()) / 2.0;
+            let mid = (first.position() + second.position()) / 2.0;
+            let mid_delta = mid - previous_mid;
+
+            let scale = projection.scale;
+            transform.translation += Vec3::new(-mid_delta.x, mid_delta.y, 0.0) * scale;
+
+            let new_scale = (scale * previous_dist / dist)
+                .clamp(MIN_SCALE, controller.max_scale.max(MIN_SCALE));
+            let anchor = screen_offset(&window, mid);
+            transform.translation += Vec3::from((anchor * (scale - new_scale), 0.0));
+
+            projection.scale = new_scale;
+            controller.target_scale = new_scale;
+            controller.zoom_anchor = None;
+        }
+    }
+}
+
+fn fit_maze_to_view(
+    bounds: Res<MazeBounds>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    camera: Single<(
+        &mut Transform,
+        &mut OrthographicProjection,
+        &mut CameraController,
+    )>,
+) {
+    let (mut transform, mut projection, mut controller) = camera.into_inner();
+
+    let window_size = window.size();
+    if window_size.min_element() <= 0.0 {
+        return;
+    }
+
+    let fit_scale = (bounds.size() / window_size).max_element() * FIT_MARGIN;
+
+    transform.translation = Vec3::from((bounds.center(), transform.translation.z));
+    projection.scale = fit_scale;
+    controller.target_scale = fit_scale;
+    controller.zoom_anchor = None;
+    controller.max_scale = fit_scale * MAX_ZOOM_OUT;
+}
+
+/// Keeps the center of the view over the maze so it can't be lost off screen.
+fn clamp_to_maze(
+    bounds: Res<MazeBounds>,
+    mut camera: Single<&mut Transform, With<CameraController>>,
+) {
+    let position = camera.translation.truncate().clamp(bounds.min, bounds.max);
+
+    camera.translation = Vec3::from((position, camera.translation.z));
+}
+
+/// Converts a window position (origin top left, y down) into an offset from the
+/// window center in world orientation (y up), in logical pixels.
+fn screen_offset(window: &Window, position: Vec2) -> Vec2 {
+    Vec2 {
+        x: position.x - window.width() / 2.0,
+        y: window.height() / 2.0 - position.y,
+    }
+}
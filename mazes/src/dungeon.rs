@@ -0,0 +1,521 @@
+use std::{fs, io};
+
+use bevy::prelude::*;
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{coord_to_idx, idx_to_coord, theme::Theme, update, Direction, Maze, Step};
+
+pub struct DungeonPlugin;
+
+impl Plugin for DungeonPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ExportDungeonEvent>()
+            .init_resource::<DungeonConfig>()
+            .add_systems(
+                Update,
+                (
+                    advance_dungeon
+                        .after(update)
+                        .run_if(resource_exists::<Maze>)
+                        .run_if(resource_exists::<Dungeon>),
+                    export_dungeon
+                        .run_if(resource_exists::<Maze>)
+                        .run_if(resource_exists::<Dungeon>)
+                        .run_if(on_event::<ExportDungeonEvent>),
+                ),
+            );
+    }
+}
+
+/// Chance for a connector between two already connected areas to still get a door.
+const EXTRA_DOOR_CHANCE: f64 = 0.05;
+
+#[derive(Event, Default)]
+pub struct ExportDungeonEvent;
+
+#[derive(Resource)]
+pub struct DungeonConfig {
+    pub room_count: usize,
+    pub room_min_size: u32,
+    pub room_max_size: u32,
+    pub export_path: String,
+    pub status: String,
+}
+
+impl Default for DungeonConfig {
+    fn default() -> Self {
+        Self {
+            room_count: 8,
+            room_min_size: 3,
+            room_max_size: 7,
+            export_path: String::from("dungeon.txt"),
+            status: String::new(),
+        }
+    }
+}
+
+/// Rooms laid over a maze whose corridors are carved by the backtracker.
+///
+/// Room cells are marked as visited before generation starts, so the
+/// backtracker carves around them. Once every corridor region is filled the
+/// rooms get doors and the dead ends of the corridors are filled in.
+#[derive(Resource)]
+pub struct Dungeon {
+    /// Rooms as cell rectangles, `max` is exclusive.
+    rooms: Vec<URect>,
+    doors: Vec<(UVec2, UVec2)>,
+    /// Cells filled in by dead end pruning.
+    solid: Vec<bool>,
+    finished: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Tile {
+    Wall,
+    Floor,
+    Door,
+}
+
+impl Tile {
+    fn symbol(self) -> char {
+        match self {
+            Tile::Wall => '#',
+            Tile::Floor => '.',
+            Tile::Door => '+',
+        }
+    }
+}
+
+/// Randomly places up to `room_count` rooms that don't touch each other.
+pub fn place_rooms(width: usize, height: usize, config: &DungeonConfig) -> Vec<URect> {
+    let mut rng = rand::rng();
+    let mut rooms: Vec<URect> = Vec::new();
+
+    let min_size = config.room_min_size.max(1);
+    let max_size = config.room_max_size.max(min_size);
+
+    for _ in 0..config.room_count * 10 {
+        if rooms.len() == config.room_count {
+            break;
+        }
+
+        let size = UVec2 {
+            x: rng.random_range(min_size..=max_size),
+            y: rng.random_range(min_size..=max_size),
+        };
+
+        // Keep a border of corridor cells around the maze
+        if size.x + 2 > width as u32 || size.y + 2 > height as u32 {
+            continue;
+        }
+
+        let min = UVec2 {
+            x: rng.random_range(1..=width as u32 - size.x - 1),
+            y: rng.random_range(1..=height as u32 - size.y - 1),
+        };
+        let room = URect::from_corners(min, min + size);
+
+        // Rooms are at least one cell apart so corridors can run between them
+        let overlaps = rooms.iter().any(|other| {
+            room.min.x <= other.max.x
+                && other.min.x <= room.max.x
+                && room.min.y <= other.max.y
+                && other.min.y <= room.max.y
+        });
+
+        if !overlaps {
+            rooms.push(room);
+        }
+    }
+
+    rooms
+}
+
+pub fn room_contains(room: &URect, coord: UVec2) -> bool {
+    coord.cmpge(room.min).all() && coord.cmplt(room.max).all()
+}
+
+impl Dungeon {
+    /// Opens up the rooms in `maze` and points the backtracker at the first corridor cell.
+    pub fn new(rooms: Vec<URect>, maze: &mut Maze, commands: &mut Commands) -> Self {
+        for room in &rooms {
+            for y in room.min.y..room.max.y {
+                for x in room.min.x..room.max.x {
+                    let coord = UVec2::new(x, y);
+                    maze.cells[coord_to_idx(coord, maze.width)].visited = true;
+
+                    for direction in [Direction::Right, Direction::Up] {
+                        let Some(neighbour) = maze.neighbour(coord, direction) else {
+                            continue;
+                        };
+
+                        if room_contains(room, neighbour) {
+                            maze.open_passage(
+                                commands,
+                                Step {
+                                    from_coord: coord,
+                                    to_coord: neighbour,
+                                    opend_walls: true,
+                                },
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(idx) = maze.cells.iter().position(|cell| !cell.visited) {
+            maze.start_region(idx_to_coord(idx, maze.width));
+        }
+
+        Self {
+            rooms,
+            doors: Vec::new(),
+            solid: vec![false; maze.cells.len()],
+            finished: false,
+        }
+    }
+
+    pub fn is_room(&self, coord: UVec2) -> bool {
+        self.room_at(coord).is_some()
+    }
+
+    pub fn is_solid(&self, idx: usize) -> bool {
+        self.solid[idx]
+    }
+
+    fn room_at(&self, coord: UVec2) -> Option<usize> {
+        self.rooms
+            .iter()
+            .position(|room| room_contains(room, coord))
+    }
+
+    /// Labels every cell with the area it belongs to, following open passages.
+    fn areas(&self, maze: &Maze) -> Vec<usize> {
+        let mut areas = vec![usize::MAX; maze.cells.len()];
+        let mut area_count = 0;
+
+        for start in 0..maze.cells.len() {
+            if areas[start] != usize::MAX {
+                continue;
+            }
+
+            let mut stack = vec![idx_to_coord(start, maze.width)];
+            areas[start] = area_count;
+
+            while let Some(coord) = stack.pop() {
+                for direction in Direction::ALL {
+                    let Some(neighbour) = maze.neighbour(coord, direction) else {
+                        continue;
+                    };
+
+                    let idx = coord_to_idx(neighbour, maze.width);
+                    if areas[idx] == usize::MAX && maze.is_open(coord, direction) {
+                        areas[idx] = area_count;
+                        stack.push(neighbour);
+                    }
+                }
+            }
+
+            area_count += 1;
+        }
+
+        areas
+    }
+
+    /// Adds doors between rooms and corridors until everything is connected.
+    fn connect_rooms(&mut self, maze: &mut Maze, commands: &mut Commands) {
+        let mut rng = rand::rng();
+        let areas = self.areas(maze);
+
+        // Every wall between a room and something outside of it can become a door
+        let mut connectors = Vec::new();
+        for (idx, cell_area) in areas.iter().enumerate() {
+            let coord = idx_to_coord(idx, maze.width);
+
+            for direction in [Direction::Right, Direction::Up] {
+                let Some(neighbour) = maze.neighbour(coord, direction) else {
+                    continue;
+                };
+
+                let neighbour_area = areas[coord_to_idx(neighbour, maze.width)];
+                let touches_room =
+                    self.room_at(coord).is_some() || self.room_at(neighbour).is_some();
+
+                if touches_room && *cell_area != neighbour_area {
+                    connectors.push((coord, neighbour));
+                }
+            }
+        }
+
+        connectors.shuffle(&mut rng);
+
+        // Union find over the areas
+        let mut parents: Vec<usize> = (0..maze.cells.len()).collect();
+        fn find(parents: &mut [usize], area: usize) -> usize {
+            let mut root = area;
+            while parents[root] != root {
+                root = parents[root];
+            }
+            parents[area] = root;
+            root
+        }
+
+        for (from, to) in connectors {
+            let from_area = find(&mut parents, areas[coord_to_idx(from, maze.width)]);
+            let to_area = find(&mut parents, areas[coord_to_idx(to, maze.width)]);
+
+            if from_area == to_area && !rng.random_bool(EXTRA_DOOR_CHANCE) {
+                continue;
+            }
+
+            parents[from_area] = to_area;
+
+            maze.open_passage(
+                commands,
+                Step {
+                    from_coord: from,
+                    to_coord: to,
+                    opend_walls: true,
+                },
+            );
+            self.doors.push((from, to));
+        }
+    }
+
+    /// Fills in corridor cells with a single way out until no dead end is left.
+    ///
+    /// Without rooms every corridor ends up a dead end, so the maze is kept whole.
+    fn prune_dead_ends(&mut self, maze: &Maze) {
+        if self.rooms.is_empty() {
+            return;
+        }
+
+        let mut candidates: Vec<UVec2> = (0..maze.cells.len())
+            .map(|idx| idx_to_coord(idx, maze.width))
+            .collect();
+
+        while let Some(coord) = candidates.pop() {
+            let idx = coord_to_idx(coord, maze.width);
+            if self.solid[idx] || self.room_at(coord).is_some() {
+                continue;
+            }
+
+            let exits = self.exits(maze, coord);
+            if exits.len() > 1 {
+                continue;
+            }
+
+            self.solid[idx] = true;
+            candidates.extend(exits);
+        }
+    }
+
+    fn exits(&self, maze: &Maze, coord: UVec2) -> Vec<UVec2> {
+        Direction::ALL
+            .into_iter()
+            .filter(|direction| maze.is_open(coord, *direction))
+            .filter_map(|direction| maze.neighbour(coord, direction))
+            .filter(|neighbour| !self.solid[coord_to_idx(*neighbour, maze.width)])
+            .collect()
+    }
+
+    fn is_door(&self, from: UVec2, to: UVec2) -> bool {
+        self.doors
+            .iter()
+            .any(|door| *door == (from, to) || *door == (to, from))
+    }
+
+    /// Grid of `2 * width + 1` by `2 * height + 1` tiles, with cells on odd
+    /// coordinates and the walls between them on even ones. Row 0 is the bottom.
+    pub fn tiles(&self, maze: &Maze) -> Vec<Vec<Tile>> {
+        let mut tiles = vec![vec![Tile::Wall; 2 * maze.width + 1]; 2 * maze.height + 1];
+
+        for (idx, solid) in self.solid.iter().enumerate() {
+            if *solid {
+                continue;
+            }
+
+            let coord = idx_to_coord(idx, maze.width);
+            let tile = coord * 2 + UVec2::ONE;
+            tiles[tile.y as usize][tile.x as usize] = Tile::Floor;
+
+            for direction in [Direction::Right, Direction::Up] {
+                let Some(neighbour) = maze.neighbour(coord, direction) else {
+                    continue;
+                };
+
+                if self.solid[coord_to_idx(neighbour, maze.width)]
+                    || !maze.is_open(coord, direction)
+                {
+                    continue;
+                }
+
+                let between = (tile.as_ivec2() + direction.to_coord()).as_uvec2();
+                tiles[between.y as usize][between.x as usize] = if self.is_door(coord, neighbour) {
+                    Tile::Door
+                } else {
+                    Tile::Floor
+                };
+            }
+        }
+
+        tiles
+    }
+
+    fn write_tiles(&self, maze: &Maze, path: &str) -> io::Result<()> {
+        let text = self
+            .tiles(maze)
+            .iter()
+            .rev()
+            .map(|row| row.iter().map(|tile| tile.symbol()).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        fs::write(path, text + "\n")
+    }
+}
+
+fn advance_dungeon(
+    mut commands: Commands,
+    mut maze: ResMut<Maze>,
+    mut dungeon: ResMut<Dungeon>,
+    theme: Res<Theme>,
+    mut cells: Query<&mut Sprite>,
+) {
+    if dungeon.finished || !maze.complete() {
+        return;
+    }
+
+    // Rooms can split the corridors into several regions, each needs its own run
+    if let Some(idx) = maze.cells.iter().position(|cell| !cell.visited) {
+        let coord = idx_to_coord(idx, maze.width);
+        maze.start_region(coord);
+        return;
+    }
+
+    dungeon.connect_rooms(&mut maze, &mut commands);
+    dungeon.prune_dead_ends(&maze);
+    dungeon.finished = true;
+
+    for (cell, solid) in maze.cells.iter().zip(&dungeon.solid) {
+        if *solid {
+            cells.get_mut(cell.entity).unwrap().color = theme.palette.solid.into();
+        }
+    }
+}
+
+fn export_dungeon(
+    maze: Res<Maze>,
+    dungeon: Res<Dungeon>,
+    mut dungeon_config: ResMut<DungeonConfig>,
+) {
+    if !dungeon.finished {
+        dungeon_config.status = String::from("Dungeon is not finished yet");
+        return;
+    }
+
+    dungeon_config.status = match dungeon.write_tiles(&maze, &dungeon_config.export_path) {
+        Ok(()) => format!("Exported {}", dungeon_config.export_path),
+        Err(e) => format!("Export failed: {e}"),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::world::CommandQueue;
+
+    use super::*;
+    use crate::{Cell, Walls};
+
+    /// Maze with every wall standing, its entities spawned bare in `world`.
+    fn walled_maze(world: &mut World, width: usize, height: usize) -> Maze {
+        let cells = (0..width * height)
+            .map(|_| Cell {
+                entity: world.spawn_empty().id(),
+                walls: Walls {
+                    up: Some(world.spawn_empty().id()),
+                    down: Some(world.spawn_empty().id()),
+                    left: Some(world.spawn_empty().id()),
+                    right: Some(world.spawn_empty().id()),
+                },
+                visited: false,
+            })
+            .collect();
+
+        Maze {
+            cells,
+            width,
+            height,
+            stack: vec![UVec2::ZERO],
+            steps: 0,
+        }
+    }
+
+    /// Carves every corridor region, then connects and prunes as `advance_dungeon` does.
+    fn generate(rooms: Vec<URect>, width: usize, height: usize) -> (Maze, Dungeon) {
+        let mut world = World::new();
+        let mut maze = walled_maze(&mut world, width, height);
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+
+        let mut dungeon = Dungeon::new(rooms, &mut maze, &mut commands);
+        loop {
+            while !maze.complete() {
+                let step = maze.step();
+                if step.opend_walls {
+                    maze.open_passage(&mut commands, step);
+                }
+            }
+
+            let Some(idx) = maze.cells.iter().position(|cell| !cell.visited) else {
+                break;
+            };
+            maze.start_region(idx_to_coord(idx, maze.width));
+        }
+
+        dungeon.connect_rooms(&mut maze, &mut commands);
+        dungeon.prune_dead_ends(&maze);
+
+        (maze, dungeon)
+    }
+
+    #[test]
+    fn maze_without_rooms_is_kept() {
+        let (maze, dungeon) = generate(Vec::new(), 9, 7);
+
+        assert!(dungeon.solid.iter().all(|solid| !solid));
+
+        let floors = dungeon
+            .tiles(&maze)
+            .iter()
+            .flatten()
+            .filter(|tile| **tile == Tile::Floor)
+            .count();
+        // Every cell, and the cells - 1 passages of the perfect maze between them
+        assert_eq!(floors, 2 * maze.cells.len() - 1);
+    }
+
+    #[test]
+    fn rooms_are_connected() {
+        let rooms = vec![
+            URect::new(1, 1, 4, 4),
+            URect::new(6, 1, 10, 3),
+            URect::new(2, 6, 5, 10),
+            URect::new(7, 5, 10, 8),
+        ];
+
+        for _ in 0..20 {
+            let (maze, dungeon) = generate(rooms.clone(), 12, 12);
+            let areas = dungeon.areas(&maze);
+            let room_area = areas[coord_to_idx(rooms[0].min, maze.width)];
+
+            for (idx, area) in areas.iter().enumerate() {
+                if !dungeon.solid[idx] {
+                    assert_eq!(*area, room_area, "{:?} is cut off", idx_to_coord(idx, 12));
+                }
+            }
+            assert!(!dungeon.doors.is_empty());
+        }
+    }
+}
@@ -0,0 +1,286 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter},
+};
+
+use bevy::{
+    prelude::*,
+    sprite::Anchor,
+    tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task},
+};
+
+use crate::{
+    camera::{FitMazeEvent, MazeBounds},
+    packed::{MazeFile, PackedMaze, OPEN_DOWN, OPEN_RIGHT},
+    sidewinder,
+    theme::Theme,
+    Maze, ResetMazeEvent,
+};
+
+pub struct LargeMazePlugin;
+
+impl Plugin for LargeMazePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<GenerateLargeMazeEvent>()
+            .add_event::<OpenLargeMazeEvent>()
+            .init_resource::<LargeMazeConfig>()
+            .add_systems(
+                Update,
+                (
+                    start_generation.run_if(on_event::<GenerateLargeMazeEvent>),
+                    poll_generation.run_if(resource_exists::<GenerationTask>),
+                    open_large_maze.run_if(on_event::<OpenLargeMazeEvent>),
+                    close_large_maze.run_if(on_event::<ResetMazeEvent>),
+                    (update_visible_window, draw_large_maze)
+                        .chain()
+                        .run_if(resource_exists::<LargeMaze>),
+                    apply_theme
+                        .run_if(resource_exists::<LargeMaze>)
+                        .run_if(resource_changed::<Theme>),
+                ),
+            );
+    }
+}
+
+/// Above this many cells on screen the walls are not drawn anymore.
+const MAX_VISIBLE_CELLS: u64 = 160 * 160;
+
+#[derive(Event, Default)]
+pub struct GenerateLargeMazeEvent;
+
+#[derive(Event, Default)]
+pub struct OpenLargeMazeEvent;
+
+#[derive(Resource)]
+pub struct LargeMazeConfig {
+    pub width: u32,
+    pub height: u32,
+    pub path: String,
+    pub status: String,
+}
+
+impl Default for LargeMazeConfig {
+    fn default() -> Self {
+        Self {
+            width: 10_000,
+            height: 10_000,
+            path: String::from("large_maze.bin"),
+            status: String::new(),
+        }
+    }
+}
+
+#[derive(Resource)]
+struct GenerationTask(Task<io::Result<()>>);
+
+/// Maze streamed from disk, only the cells around the view are loaded.
+#[derive(Resource)]
+struct LargeMaze {
+    file: MazeFile<BufReader<File>>,
+    /// Loaded cells and the coordinate of their first cell.
+    window: Option<(UVec2, PackedMaze)>,
+    visible_min: UVec2,
+    visible_max: UVec2,
+    background: Entity,
+}
+
+fn start_generation(mut commands: Commands, mut config: ResMut<LargeMazeConfig>) {
+    let path = config.path.clone();
+    let width = config.width.max(2);
+    let height = config.height.max(2);
+
+    let task = AsyncComputeTaskPool::get().spawn(async move {
+        let file = File::create(path)?;
+        sidewinder::generate(BufWriter::new(file), width, height)
+    });
+
+    config.status = format!("Generating {width}x{height} maze...");
+    commands.insert_resource(GenerationTask(task));
+}
+
+fn poll_generation(
+    mut commands: Commands,
+    mut task: ResMut<GenerationTask>,
+    mut config: ResMut<LargeMazeConfig>,
+    mut open_event: EventWriter<OpenLargeMazeEvent>,
+) {
+    let Some(result) = block_on(future::poll_once(&mut task.0)) else {
+        return;
+    };
+
+    commands.remove_resource::<GenerationTask>();
+
+    match result {
+        Ok(()) => {
+            config.status = format!("Generated {}", config.path);
+            open_event.send_default();
+        }
+        Err(e) => config.status = format!("Generation failed: {e}"),
+    }
+}
+
+fn open_large_maze(
+    mut commands: Commands,
+    mut config: ResMut<LargeMazeConfig>,
+    theme: Res<Theme>,
+    maze: Option<Res<Maze>>,
+    large_maze: Option<Res<LargeMaze>>,
+    mut fit_event: EventWriter<FitMazeEvent>,
+) {
+    let file = match File::open(&config.path).and_then(|file| MazeFile::open(BufReader::new(file)))
+    {
+        Ok(file) => file,
+        Err(e) => {
+            config.status = format!("Could not open {}: {e}", config.path);
+            return;
+        }
+    };
+
+    if let Some(maze) = maze {
+        maze.despawn(&mut commands);
+        commands.remove_resource::<Maze>();
+    }
+
+    if let Some(large_maze) = large_maze {
+        commands.entity(large_maze.background).despawn();
+    }
+
+    let size = maze_size(&file, theme.cell_size);
+
+    let background = commands
+        .spawn((
+            Sprite {
+                color: theme.palette.unvisited.into(),
+                custom_size: Some(size),
+                anchor: Anchor::BottomLeft,
+                ..Default::default()
+            },
+            Transform::default(),
+        ))
+        .id();
+
+    config.status = format!("Viewing {}x{} maze", file.width(), file.height());
+
+    commands.insert_resource(LargeMaze {
+        file,
+        window: None,
+        visible_min: UVec2::ZERO,
+        visible_max: UVec2::ZERO,
+        background,
+    });
+    commands.insert_resource(MazeBounds(Rect::from_corners(Vec2::ZERO, size)));
+    fit_event.send_default();
+}
+
+fn maze_size<R>(file: &MazeFile<R>, cell_size: f32) -> Vec2 {
+    Vec2 {
+        x: file.width() as f32 * cell_size,
+        y: file.height() as f32 * cell_size,
+    }
+}
+
+fn apply_theme(
+    mut commands: Commands,
+    large_maze: Res<LargeMaze>,
+    theme: Res<Theme>,
+    mut background: Query<&mut Sprite>,
+) {
+    let size = maze_size(&large_maze.file, theme.cell_size);
+
+    if let Ok(mut sprite) = background.get_mut(large_maze.background) {
+        sprite.color = theme.palette.unvisited.into();
+        sprite.custom_size = Some(size);
+    }
+
+    commands.insert_resource(MazeBounds(Rect::from_corners(Vec2::ZERO, size)));
+}
+
+fn close_large_maze(mut commands: Commands, large_maze: Option<Res<LargeMaze>>) {
+    if let Some(large_maze) = large_maze {
+        commands.entity(large_maze.background).despawn();
+        commands.remove_resource::<LargeMaze>();
+    }
+}
+
+fn update_visible_window(
+    mut large_maze: ResMut<LargeMaze>,
+    mut config: ResMut<LargeMazeConfig>,
+    theme: Res<Theme>,
+    camera: Single<(&Transform, &OrthographicProjection), With<Camera2d>>,
+) {
+    let (transform, projection) = camera.into_inner();
+
+    let cell_size = theme.cell_size;
+    let camera_pos = transform.translation.truncate();
+    let maze_size = UVec2::new(large_maze.file.width(), large_maze.file.height());
+
+    let visible_min = ((projection.area.min + camera_pos) / cell_size)
+        .floor()
+        .max(Vec2::ZERO)
+        .as_uvec2()
+        .min(maze_size);
+    let visible_max = ((projection.area.max + camera_pos) / cell_size)
+        .ceil()
+        .max(Vec2::ZERO)
+        .as_uvec2()
+        .min(maze_size);
+
+    let visible = visible_max - visible_min;
+    if visible.x as u64 * visible.y as u64 > MAX_VISIBLE_CELLS {
+        large_maze.window = None;
+        return;
+    }
+
+    large_maze.visible_min = visible_min;
+    large_maze.visible_max = visible_max;
+
+    if let Some((origin, window)) = &large_maze.window {
+        let window_max = *origin + UVec2::new(window.width() as u32, window.height() as u32);
+        if visible_min.cmpge(*origin).all() && visible_max.cmple(window_max).all() {
+            return;
+        }
+    }
+
+    // Load a margin around the view so that small pans don't hit the disk
+    let origin = visible_min.saturating_sub(visible);
+    match large_maze.file.read_window(origin, visible * 3) {
+        Ok(window) => large_maze.window = Some((origin, window)),
+        Err(e) => {
+            config.status = format!("Could not read maze: {e}");
+            large_maze.window = None;
+        }
+    }
+}
+
+fn draw_large_maze(large_maze: Res<LargeMaze>, theme: Res<Theme>, mut gizmos: Gizmos) {
+    let cell_size = theme.cell_size;
+    let color = theme.palette.wall;
+
+    let size = maze_size(&large_maze.file, cell_size);
+
+    // Left and top borders are not stored in the cells
+    gizmos.line_2d(Vec2::ZERO, Vec2::new(0.0, size.y), color);
+    gizmos.line_2d(Vec2::new(0.0, size.y), size, color);
+
+    let Some((origin, window)) = &large_maze.window else {
+        return;
+    };
+
+    for y in large_maze.visible_min.y..large_maze.visible_max.y {
+        for x in large_maze.visible_min.x..large_maze.visible_max.x {
+            let coord = UVec2::new(x, y) - *origin;
+            let corner = Vec2::new(x as f32, y as f32) * cell_size;
+
+            let cell = window.cell(coord);
+
+            if cell & OPEN_DOWN == 0 {
+                gizmos.line_2d(corner, corner + Vec2::new(cell_size, 0.0), color);
+            }
+
+            if cell & OPEN_RIGHT == 0 {
+                let right = corner + Vec2::new(cell_size, 0.0);
+                gizmos.line_2d(right, right + Vec2::new(0.0, cell_size), color);
+            }
+        }
+    }
+}
@@ -1,14 +1,22 @@
-use bevy::{
-    color::palettes,
-    input::mouse::{MouseMotion, MouseWheel},
-    prelude::*,
-};
+use bevy::prelude::*;
 use bevy_egui::{
     egui::{self, DragValue},
     EguiContexts, EguiPlugin,
 };
 
-const CELL_SIZE: usize = 32;
+mod camera;
+mod dungeon;
+mod large;
+mod overlay;
+mod packed;
+mod sidewinder;
+mod theme;
+
+use camera::{CameraPlugin, FitMazeEvent, MazeBounds};
+use dungeon::{Dungeon, DungeonConfig, DungeonPlugin, ExportDungeonEvent};
+use large::{GenerateLargeMazeEvent, LargeMazeConfig, LargeMazePlugin, OpenLargeMazeEvent};
+use overlay::OverlayPlugin;
+use theme::{CellImage, Theme, ThemePlugin};
 
 fn main() {
     App::new()
@@ -20,7 +28,14 @@ fn main() {
             ..Default::default()
         }))
         // .add_plugins(DefaultPlugins)
-        .add_plugins(EguiPlugin)
+        .add_plugins((
+            EguiPlugin,
+            CameraPlugin,
+            LargeMazePlugin,
+            DungeonPlugin,
+            OverlayPlugin,
+            ThemePlugin,
+        ))
         .add_event::<ResetMazeEvent>()
         .init_resource::<MazeConfig>()
         .add_systems(Startup, setup)
@@ -28,7 +43,6 @@ fn main() {
             Update,
             (
                 exit_on_escape,
-                pan_and_zoom,
                 ui,
                 toggle_pause,
                 update.run_if(resource_exists::<Maze>),
@@ -38,7 +52,16 @@ fn main() {
         .run();
 }
 
-fn exit_on_escape(input: Res<ButtonInput<KeyCode>>, mut exit_event: EventWriter<AppExit>) {
+fn exit_on_escape(
+    mut ctx: EguiContexts,
+    input: Res<ButtonInput<KeyCode>>,
+    mut exit_event: EventWriter<AppExit>,
+) {
+    // Keys typed in the panel's text fields are theirs
+    if ctx.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+
     if input.just_pressed(KeyCode::Escape) {
         exit_event.send_default();
     }
@@ -54,17 +77,35 @@ enum SolvingMode {
     Stepping,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum GenerationMode {
+    Maze,
+    Dungeon,
+}
+
 #[derive(Resource)]
 struct MazeConfig {
     width: usize,
     height: usize,
     solving_mode: SolvingMode,
+    generation_mode: GenerationMode,
+    show_overlay: bool,
 }
 
+#[allow(clippy::too_many_arguments)]
 fn ui(
+    time: Res<Time>,
     mut ctx: EguiContexts,
     mut maze_config: ResMut<MazeConfig>,
+    maze: Option<Res<Maze>>,
     mut reset_event: EventWriter<ResetMazeEvent>,
+    mut fit_event: EventWriter<FitMazeEvent>,
+    mut large_maze_config: ResMut<LargeMazeConfig>,
+    mut generate_large_maze_event: EventWriter<GenerateLargeMazeEvent>,
+    mut open_large_maze_event: EventWriter<OpenLargeMazeEvent>,
+    mut dungeon_config: ResMut<DungeonConfig>,
+    mut export_dungeon_event: EventWriter<ExportDungeonEvent>,
+    mut theme: ResMut<Theme>,
 ) {
     egui::SidePanel::left("Sied panel").show(ctx.ctx_mut(), |ui| {
         ui.heading("Maze Generation");
@@ -77,10 +118,54 @@ fn ui(
             maze_config.height = maze_config.height.max(2)
         });
 
-        if ui.button("Reset Maze").clicked() {
-            reset_event.send_default();
+        ui.horizontal(|ui| {
+            ui.selectable_value(
+                &mut maze_config.generation_mode,
+                GenerationMode::Maze,
+                "Maze",
+            );
+            ui.selectable_value(
+                &mut maze_config.generation_mode,
+                GenerationMode::Dungeon,
+                "Dungeon",
+            );
+        });
+
+        if maze_config.generation_mode == GenerationMode::Dungeon {
+            ui.horizontal(|ui| {
+                ui.label("Rooms");
+                ui.add(DragValue::new(&mut dungeon_config.room_count));
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Room size");
+                ui.add(DragValue::new(&mut dungeon_config.room_min_size).range(1..=100));
+                ui.add(DragValue::new(&mut dungeon_config.room_max_size).range(1..=100));
+            });
+
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut dungeon_config.export_path);
+
+                if ui.button("Export tiles").clicked() {
+                    export_dungeon_event.send_default();
+                }
+            });
+
+            if !dungeon_config.status.is_empty() {
+                ui.label(&dungeon_config.status);
+            }
         }
 
+        ui.horizontal(|ui| {
+            if ui.button("Reset Maze").clicked() {
+                reset_event.send_default();
+            }
+
+            if ui.button("Fit to view").clicked() {
+                fit_event.send_default();
+            }
+        });
+
         ui.separator();
 
         ui.selectable_value(&mut maze_config.solving_mode, SolvingMode::Paused, "Pause");
@@ -90,6 +175,65 @@ fn ui(
             SolvingMode::Stepping,
             "Stepping",
         );
+
+        if let Some(maze) = maze {
+            ui.separator();
+
+            ui.checkbox(&mut maze_config.show_overlay, "Show progress overlay");
+
+            let visited = maze.visited_count();
+            let remaining_steps = maze.remaining_steps();
+
+            ui.label(format!("Visited cells: {visited} / {}", maze.cells.len()));
+            ui.label(format!("Stack depth: {}", maze.stack.len()));
+            ui.label(format!("Steps taken: {}", maze.steps));
+            ui.label(format!(
+                "Completion: {:.1}% ({remaining_steps} steps left)",
+                100.0 * visited as f32 / maze.cells.len() as f32
+            ));
+
+            // The backtracker does one step per frame while running
+            if maze_config.solving_mode == SolvingMode::Running && remaining_steps > 0 {
+                let eta = remaining_steps as f32 * time.delta_secs();
+                ui.label(format!("Estimated time left: {eta:.1}s"));
+            }
+        }
+
+        ui.separator();
+
+        ui.collapsing("Theme", |ui| {
+            // Only write back real edits so the theme isn't re-applied every frame
+            let mut edited_theme = theme.clone();
+            edited_theme.ui(ui);
+            if edited_theme != *theme {
+                *theme = edited_theme;
+            }
+        });
+
+        ui.separator();
+
+        ui.heading("Large Maze");
+
+        ui.horizontal(|ui| {
+            ui.add(DragValue::new(&mut large_maze_config.width));
+            ui.add(DragValue::new(&mut large_maze_config.height));
+        });
+
+        ui.text_edit_singleline(&mut large_maze_config.path);
+
+        ui.horizontal(|ui| {
+            if ui.button("Generate").clicked() {
+                generate_large_maze_event.send_default();
+            }
+
+            if ui.button("Open").clicked() {
+                open_large_maze_event.send_default();
+            }
+        });
+
+        if !large_maze_config.status.is_empty() {
+            ui.label(&large_maze_config.status);
+        }
     });
 }
 
@@ -99,6 +243,7 @@ struct Maze {
     width: usize,
     height: usize,
     stack: Vec<UVec2>,
+    steps: usize,
 }
 
 #[derive(Clone, Copy)]
@@ -143,11 +288,31 @@ impl Default for MazeConfig {
             width: 30,
             height: 30,
             solving_mode: SolvingMode::Paused,
+            generation_mode: GenerationMode::Maze,
+            show_overlay: true,
+        }
+    }
+}
+
+impl Walls {
+    fn get(&self, direction: Direction) -> Option<Entity> {
+        match direction {
+            Direction::Up => self.up,
+            Direction::Down => self.down,
+            Direction::Left => self.left,
+            Direction::Right => self.right,
         }
     }
 }
 
 impl Direction {
+    const ALL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+    ];
+
     fn to_coord(&self) -> IVec2 {
         match self {
             Direction::Up => IVec2::Y,
@@ -173,39 +338,12 @@ impl Direction {
 
 impl Maze {
     fn step(&mut self) -> Step {
+        self.steps += 1;
         self.mark_current_cell();
 
         let current_coord = *self.stack.last().unwrap();
 
-        let directions = [
-            Direction::Up,
-            Direction::Down,
-            Direction::Left,
-            Direction::Right,
-        ]
-        .into_iter()
-        .filter(|direction| {
-            let dir = direction.to_coord();
-
-            let new_coord = dir + current_coord.as_ivec2();
-            let outside_maze = new_coord.x < 0
-                || new_coord.x >= self.width as i32
-                || new_coord.y < 0
-                || new_coord.y >= self.height as i32;
-
-            if outside_maze {
-                return false;
-            }
-
-            let idx = coord_to_idx(new_coord.as_uvec2(), self.width);
-            let next_cell = self.cells[idx];
-            if next_cell.visited {
-                return false;
-            }
-
-            true
-        })
-        .collect::<Vec<Direction>>();
+        let directions = self.unvisited_directions(current_coord);
 
         if directions.is_empty() {
             self.stack.pop();
@@ -231,6 +369,44 @@ impl Maze {
         }
     }
 
+    fn neighbour(&self, coord: UVec2, direction: Direction) -> Option<UVec2> {
+        let new_coord = coord.as_ivec2() + direction.to_coord();
+        let outside_maze = new_coord.x < 0
+            || new_coord.x >= self.width as i32
+            || new_coord.y < 0
+            || new_coord.y >= self.height as i32;
+
+        if outside_maze {
+            return None;
+        }
+
+        Some(new_coord.as_uvec2())
+    }
+
+    fn unvisited_directions(&self, coord: UVec2) -> Vec<Direction> {
+        Direction::ALL
+            .into_iter()
+            .filter(|direction| {
+                self.neighbour(coord, *direction).is_some_and(|new_coord| {
+                    !self.cells[coord_to_idx(new_coord, self.width)].visited
+                })
+            })
+            .collect()
+    }
+
+    fn is_open(&self, coord: UVec2, direction: Direction) -> bool {
+        self.cells[coord_to_idx(coord, self.width)]
+            .walls
+            .get(direction)
+            .is_none()
+    }
+
+    /// Starts the backtracker again from `coord`, used once the previous region is filled.
+    fn start_region(&mut self, coord: UVec2) {
+        self.stack = vec![coord];
+        self.mark_current_cell();
+    }
+
     fn mark_current_cell(&mut self) {
         let current_coord = self.stack.last().unwrap();
         let idx = coord_to_idx(*current_coord, self.width);
@@ -312,18 +488,44 @@ impl Maze {
     }
 
     fn complete(&self) -> bool {
-        self.cells[0].visited && self.stack.len() == 1
+        let root = self.stack[0];
+
+        self.stack.len() == 1
+            && self.cells[coord_to_idx(root, self.width)].visited
+            && self.unvisited_directions(root).is_empty()
     }
-}
 
-fn setup(mut commands: Commands, mut reset_event: EventWriter<ResetMazeEvent>) {
-    commands.spawn(Camera2d);
-    reset_event.send_default();
-}
+    /// Despawns the walls between the two cells of `step` and opens the passage.
+    fn open_passage(&mut self, commands: &mut Commands, step: Step) {
+        let width = self.width;
+        let previous_cell = self.cells[coord_to_idx(step.from_coord, width)];
+        let current_cell = self.cells[coord_to_idx(step.to_coord, width)];
 
-fn reset_maze(mut commands: Commands, maze_config: Res<MazeConfig>, maze: Option<Res<Maze>>) {
-    if let Some(maze) = maze {
-        for cell in &maze.cells {
+        #[rustfmt::skip]
+        match step.direction() {
+            Direction::Up => {
+                commands.entity(previous_cell.walls.up.unwrap()).despawn();
+                commands.entity(current_cell.walls.down.unwrap()).despawn();
+            }
+            Direction::Down => {
+                commands.entity(previous_cell.walls.down.unwrap()).despawn();
+                commands.entity(current_cell.walls.up.unwrap()).despawn();
+            }
+            Direction::Left => {
+                commands.entity(previous_cell.walls.left.unwrap()).despawn();
+                commands.entity(current_cell.walls.right.unwrap()).despawn();
+            }
+            Direction::Right => {
+                commands.entity(previous_cell.walls.right.unwrap()).despawn();
+                commands.entity(current_cell.walls.left.unwrap()).despawn();
+            }
+        };
+
+        self.open_walls(step);
+    }
+
+    fn despawn(&self, commands: &mut Commands) {
+        for cell in &self.cells {
             commands.entity(cell.entity).despawn();
             if let Some(e) = cell.walls.up {
                 commands.entity(e).despawn();
@@ -340,110 +542,133 @@ fn reset_maze(mut commands: Commands, maze_config: Res<MazeConfig>, maze: Option
         }
     }
 
-    let x_offset = CELL_SIZE * maze_config.width / 2;
-    let y_offset = CELL_SIZE * maze_config.height / 2;
+    fn visited_count(&self) -> usize {
+        self.cells.iter().filter(|cell| cell.visited).count()
+    }
+
+    /// Estimate of the steps the backtracker still needs: every unvisited cell
+    /// is pushed and popped once, and the current stack has to unwind back to
+    /// its root.
+    fn remaining_steps(&self) -> usize {
+        if self.complete() {
+            return 0;
+        }
+
+        let current_coord = *self.stack.last().unwrap();
+        let current_unvisited = !self.cells[coord_to_idx(current_coord, self.width)].visited;
+        let unvisited = self.cells.len() - self.visited_count() - current_unvisited as usize;
+
+        2 * unvisited + self.stack.len() - 1
+    }
+
+    /// World space position of the center of the cell at `coord`.
+    fn cell_position(&self, coord: UVec2, cell_size: f32) -> Vec2 {
+        Vec2 {
+            x: cell_size * coord.x as f32 - cell_size * self.width as f32 / 2.0,
+            y: cell_size * coord.y as f32 - cell_size * self.height as f32 / 2.0,
+        }
+    }
+
+    /// World space rectangle covered by the maze cells.
+    fn bounds(&self, cell_size: f32) -> Rect {
+        let min = self.cell_position(UVec2::ZERO, cell_size) - cell_size / 2.0;
+        let size = Vec2 {
+            x: cell_size * self.width as f32,
+            y: cell_size * self.height as f32,
+        };
+
+        Rect::from_corners(min, min + size)
+    }
+}
+
+fn setup(mut reset_event: EventWriter<ResetMazeEvent>) {
+    reset_event.send_default();
+}
+
+fn reset_maze(
+    mut commands: Commands,
+    maze_config: Res<MazeConfig>,
+    dungeon_config: Res<DungeonConfig>,
+    theme: Res<Theme>,
+    cell_image: Res<CellImage>,
+    maze: Option<Res<Maze>>,
+    mut fit_event: EventWriter<FitMazeEvent>,
+) {
+    if let Some(maze) = maze {
+        maze.despawn(&mut commands);
+    }
 
     let mut maze = Maze {
         cells: Vec::new(),
         width: maze_config.width,
         height: maze_config.height,
         stack: vec![UVec2::ZERO],
+        steps: 0,
+    };
+
+    let rooms = match maze_config.generation_mode {
+        GenerationMode::Maze => Vec::new(),
+        GenerationMode::Dungeon => {
+            dungeon::place_rooms(maze_config.width, maze_config.height, &dungeon_config)
+        }
     };
 
     for y in 0..maze_config.height {
         for x in 0..maze_config.width {
-            let x = (CELL_SIZE * x) as f32 - x_offset as f32;
-            let y = (CELL_SIZE * y) as f32 - y_offset as f32;
+            let coord = UVec2::new(x as u32, y as u32);
+            let color = if rooms.iter().any(|room| dungeon::room_contains(room, coord)) {
+                theme.palette.room
+            } else {
+                theme.palette.unvisited
+            };
+
+            let position = maze.cell_position(coord, theme.cell_size);
 
-            let cell = spawn_cell(&mut commands, x, y);
+            let cell = spawn_cell(
+                &mut commands,
+                &theme,
+                cell_image.0.clone(),
+                position,
+                color.into(),
+            );
             maze.cells.push(cell);
         }
     }
 
+    match maze_config.generation_mode {
+        GenerationMode::Maze => commands.remove_resource::<Dungeon>(),
+        GenerationMode::Dungeon => {
+            let dungeon = Dungeon::new(rooms, &mut maze, &mut commands);
+            commands.insert_resource(dungeon);
+        }
+    }
+
+    commands.insert_resource(MazeBounds(maze.bounds(theme.cell_size)));
     commands.insert_resource(maze);
+    fit_event.send_default();
 }
 
-fn spawn_cell(commands: &mut Commands, x: f32, y: f32) -> Cell {
-    let up = commands
-        .spawn((
-            Sprite {
-                color: palettes::basic::BLACK.into(),
-                custom_size: Some(Vec2 {
-                    x: CELL_SIZE as f32,
-                    y: 2.0,
-                }),
-                ..Default::default()
-            },
-            Transform::from_translation(Vec3 {
-                x,
-                y: y + CELL_SIZE as f32 / 2.0,
-                z: 1.0,
-            }),
-        ))
-        .id();
-
-    let down = commands
-        .spawn((
-            Sprite {
-                color: palettes::basic::BLACK.into(),
-                custom_size: Some(Vec2 {
-                    x: CELL_SIZE as f32,
-                    y: 2.0,
-                }),
-                ..Default::default()
-            },
-            Transform::from_translation(Vec3 {
-                x,
-                y: y - CELL_SIZE as f32 / 2.0,
-                z: 1.0,
-            }),
-        ))
-        .id();
-
-    let left = commands
-        .spawn((
-            Sprite {
-                color: palettes::basic::BLACK.into(),
-                custom_size: Some(Vec2 {
-                    x: 2.0,
-                    y: CELL_SIZE as f32,
-                }),
-                ..Default::default()
-            },
-            Transform::from_translation(Vec3 {
-                x: x - CELL_SIZE as f32 / 2.0,
-                y,
-                z: 1.0,
-            }),
-        ))
-        .id();
-
-    let right = commands
-        .spawn((
-            Sprite {
-                color: palettes::basic::BLACK.into(),
-                custom_size: Some(Vec2 {
-                    x: 2.0,
-                    y: CELL_SIZE as f32,
-                }),
-                ..Default::default()
-            },
-            Transform::from_translation(Vec3 {
-                x: x + CELL_SIZE as f32 / 2.0,
-                y,
-                z: 1.0,
-            }),
-        ))
-        .id();
+fn spawn_cell(
+    commands: &mut Commands,
+    theme: &Theme,
+    image: Handle<Image>,
+    position: Vec2,
+    color: Color,
+) -> Cell {
+    let up = spawn_wall(commands, theme, position, Direction::Up);
+    let down = spawn_wall(commands, theme, position, Direction::Down);
+    let left = spawn_wall(commands, theme, position, Direction::Left);
+    let right = spawn_wall(commands, theme, position, Direction::Right);
 
     let entity = commands
         .spawn((
             Sprite {
-                color: palettes::basic::AQUA.into(),
-                custom_size: Some(Vec2::splat(CELL_SIZE as f32)),
+                image,
+                color,
+                custom_size: Some(Vec2::splat(theme.cell_size)),
                 ..Default::default()
             },
-            Transform::from_translation(Vec3 { x, y, z: 0.0 }),
+            Transform::from_translation(Vec3::from((position, 0.0))),
         ))
         .id();
 
@@ -459,11 +684,33 @@ fn spawn_cell(commands: &mut Commands, x: f32, y: f32) -> Cell {
     }
 }
 
+fn spawn_wall(
+    commands: &mut Commands,
+    theme: &Theme,
+    cell_position: Vec2,
+    direction: Direction,
+) -> Entity {
+    let (size, offset) = theme.wall_layout(direction);
+
+    commands
+        .spawn((
+            Sprite {
+                color: theme.palette.wall.into(),
+                custom_size: Some(size),
+                ..Default::default()
+            },
+            Transform::from_translation(Vec3::from((cell_position + offset, 1.0))),
+        ))
+        .id()
+}
+
 fn update(
+    mut ctx: EguiContexts,
     input: Res<ButtonInput<KeyCode>>,
     mut commands: Commands,
     mut maze: ResMut<Maze>,
     maze_config: Res<MazeConfig>,
+    theme: Res<Theme>,
     mut cells: Query<&mut Sprite>,
 ) {
     if maze.complete() {
@@ -473,7 +720,9 @@ fn update(
     let should_step = match maze_config.solving_mode {
         SolvingMode::Paused => false,
         SolvingMode::Running => true,
-        SolvingMode::Stepping => input.just_pressed(KeyCode::Space),
+        SolvingMode::Stepping => {
+            input.just_pressed(KeyCode::Space) && !ctx.ctx_mut().wants_keyboard_input()
+        }
     };
 
     // let previous_cell = maze.current_cell();
@@ -491,62 +740,19 @@ fn update(
     let current_cell = maze.cells[coord_to_idx(step.to_coord, width)];
 
     if step.opend_walls {
-        #[rustfmt::skip]
-        match step.direction() {
-            Direction::Up => {
-                commands.entity(previous_cell.walls.up.unwrap()).despawn();
-                commands.entity(current_cell.walls.down.unwrap()).despawn();
-            }
-            Direction::Down => {
-                commands.entity(previous_cell.walls.down.unwrap()).despawn();
-                commands.entity(current_cell.walls.up.unwrap()).despawn();
-            }
-            Direction::Left => {
-                commands.entity(previous_cell.walls.left.unwrap()).despawn();
-                commands.entity(current_cell.walls.right.unwrap()).despawn();
-            }
-            Direction::Right => {
-                commands.entity(previous_cell.walls.right.unwrap()).despawn();
-                commands.entity(current_cell.walls.left.unwrap()).despawn();
-            }
-        };
-
-        maze.open_walls(step);
+        maze.open_passage(&mut commands, step);
     }
 
-    cells.get_mut(previous_cell.entity).unwrap().color = palettes::basic::FUCHSIA.into();
-    cells.get_mut(current_cell.entity).unwrap().color = palettes::basic::BLUE.into();
+    cells.get_mut(previous_cell.entity).unwrap().color = theme.palette.visited.into();
+    cells.get_mut(current_cell.entity).unwrap().color = theme.palette.current.into();
 }
 
-fn pan_and_zoom(
-    mouse_input: Res<ButtonInput<MouseButton>>,
-    mut camera: Single<&mut Transform, With<Camera2d>>,
-    mut projection: Single<&mut OrthographicProjection, With<Camera2d>>,
-    mut mouse_motion_event: EventReader<MouseMotion>,
-    mut mouse_wheel: EventReader<MouseWheel>,
+fn toggle_pause(
+    mut ctx: EguiContexts,
+    input: Res<ButtonInput<KeyCode>>,
+    mut maze_config: ResMut<MazeConfig>,
 ) {
-    if mouse_input.pressed(MouseButton::Middle) {
-        for mouse_motion in mouse_motion_event.read() {
-            let mut delta = mouse_motion.delta;
-            delta.x *= -1.0;
-
-            delta *= projection.scale;
-
-            camera.translation += Vec3::from((delta, 0.0));
-        }
-    }
-
-    for mouse_wheel in mouse_wheel.read() {
-        if mouse_wheel.y > 0.0 {
-            projection.scale *= 0.5;
-        } else if mouse_wheel.y < 0.0 {
-            projection.scale *= 2.0;
-        }
-    }
-}
-
-fn toggle_pause(input: Res<ButtonInput<KeyCode>>, mut maze_config: ResMut<MazeConfig>) {
-    if input.just_pressed(KeyCode::Space) {
+    if input.just_pressed(KeyCode::Space) && !ctx.ctx_mut().wants_keyboard_input() {
         maze_config.solving_mode = match maze_config.solving_mode {
             SolvingMode::Paused => SolvingMode::Running,
             SolvingMode::Running => SolvingMode::Paused,
@@ -559,9 +765,9 @@ fn coord_to_idx(coord: UVec2, width: usize) -> usize {
     coord.y as usize * width + coord.x as usize
 }
 
-fn _idx_to_coord(idx: usize, width: usize) -> UVec2 {
+fn idx_to_coord(idx: usize, width: usize) -> UVec2 {
     UVec2 {
-        x: (idx / width) as u32,
-        y: (idx % width) as u32,
+        x: (idx % width) as u32,
+        y: (idx / width) as u32,
     }
 }
@@ -0,0 +1,48 @@
+use bevy::{math::Isometry2d, prelude::*};
+
+use crate::{coord_to_idx, theme::Theme, Maze, MazeConfig};
+
+pub struct OverlayPlugin;
+
+impl Plugin for OverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            draw_overlay
+                .run_if(resource_exists::<Maze>)
+                .run_if(|maze_config: Res<MazeConfig>| maze_config.show_overlay),
+        );
+    }
+}
+
+/// Draws the backtracker stack as a path and outlines the unvisited cells it
+/// can still reach from there.
+fn draw_overlay(maze: Res<Maze>, theme: Res<Theme>, mut gizmos: Gizmos) {
+    if maze.complete() {
+        return;
+    }
+
+    gizmos.linestrip_2d(
+        maze.stack
+            .iter()
+            .map(|coord| maze.cell_position(*coord, theme.cell_size)),
+        theme.palette.stack,
+    );
+
+    let mut in_frontier = vec![false; maze.cells.len()];
+    for coord in &maze.stack {
+        for direction in maze.unvisited_directions(*coord) {
+            let neighbour = maze.neighbour(*coord, direction).unwrap();
+            let idx = coord_to_idx(neighbour, maze.width);
+
+            if !in_frontier[idx] {
+                in_frontier[idx] = true;
+                gizmos.rect_2d(
+                    Isometry2d::from_translation(maze.cell_position(neighbour, theme.cell_size)),
+                    Vec2::splat(theme.cell_size * 0.7),
+                    theme.palette.frontier,
+                );
+            }
+        }
+    }
+}
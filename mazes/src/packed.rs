@@ -0,0 +1,203 @@
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use bevy::math::UVec2;
+
+const BITS_PER_CELL: usize = 2;
+const CELLS_PER_BYTE: usize = 8 / BITS_PER_CELL;
+
+/// The passage to the cell on the right (x + 1) is open.
+pub const OPEN_RIGHT: u8 = 0b01;
+/// The passage to the cell below (y - 1) is open.
+pub const OPEN_DOWN: u8 = 0b10;
+
+const MAGIC: [u8; 4] = *b"MAZE";
+const HEADER_LEN: u64 = 12;
+
+/// Maze stored with two bits per cell.
+///
+/// Each cell only records its right and down passages, the left and up ones
+/// are read from the neighbours. A row therefore only depends on itself and
+/// the row below it, which lets mazes be generated and stored row by row.
+pub struct PackedMaze {
+    width: usize,
+    height: usize,
+    bytes: Vec<u8>,
+}
+
+impl PackedMaze {
+    /// Creates a maze with every wall closed.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            bytes: vec![0; row_len(width) * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn row_mut(&mut self, y: usize) -> &mut [u8] {
+        let len = row_len(self.width);
+        &mut self.bytes[y * len..(y + 1) * len]
+    }
+
+    pub fn cell(&self, coord: UVec2) -> u8 {
+        let len = row_len(self.width);
+        let row = &self.bytes[coord.y as usize * len..(coord.y as usize + 1) * len];
+        cell_bits(row, coord.x as usize)
+    }
+}
+
+/// Number of bytes used to store a row of `width` cells.
+pub fn row_len(width: usize) -> usize {
+    (width * BITS_PER_CELL).div_ceil(8)
+}
+
+pub fn cell_bits(row: &[u8], x: usize) -> u8 {
+    let shift = (x % CELLS_PER_BYTE) * BITS_PER_CELL;
+    (row[x / CELLS_PER_BYTE] >> shift) & 0b11
+}
+
+pub fn set_cell_bits(row: &mut [u8], x: usize, bits: u8) {
+    let shift = (x % CELLS_PER_BYTE) * BITS_PER_CELL;
+    row[x / CELLS_PER_BYTE] |= (bits & 0b11) << shift;
+}
+
+pub fn write_header<W: Write>(writer: &mut W, width: u32, height: u32) -> io::Result<()> {
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&width.to_le_bytes())?;
+    writer.write_all(&height.to_le_bytes())
+}
+
+/// Maze file read on demand, so only the rows that are looked at are kept in memory.
+///
+/// The file is a 12 bytes header (`MAZE`, width and height as little endian
+/// `u32`) followed by the packed rows from y = 0 upwards.
+pub struct MazeFile<R> {
+    reader: R,
+    width: u32,
+    height: u32,
+}
+
+impl<R> MazeFile<R> {
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+}
+
+impl<R: Read + Seek> MazeFile<R> {
+    pub fn open(mut reader: R) -> io::Result<Self> {
+        let mut header = [0; HEADER_LEN as usize];
+        reader.read_exact(&mut header)?;
+
+        if header[0..4] != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a maze file",
+            ));
+        }
+
+        let width = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        let height = u32::from_le_bytes(header[8..12].try_into().unwrap());
+
+        Ok(Self {
+            reader,
+            width,
+            height,
+        })
+    }
+
+    /// Reads the cells in `origin..origin + size`, clamped to the maze.
+    pub fn read_window(&mut self, origin: UVec2, size: UVec2) -> io::Result<PackedMaze> {
+        let end = (origin + size).min(UVec2::new(self.width, self.height));
+        let origin = origin.min(end);
+
+        let mut window = PackedMaze::new((end.x - origin.x) as usize, (end.y - origin.y) as usize);
+
+        let len = row_len(self.width as usize);
+        let mut row = vec![0; len];
+
+        self.reader
+            .seek(SeekFrom::Start(HEADER_LEN + origin.y as u64 * len as u64))?;
+
+        for y in 0..window.height() {
+            self.reader.read_exact(&mut row)?;
+
+            let window_row = window.row_mut(y);
+            for x in 0..(end.x - origin.x) as usize {
+                set_cell_bits(window_row, x, cell_bits(&row, origin.x as usize + x));
+            }
+        }
+
+        Ok(window)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn cell_bits_round_trip() {
+        // Not a multiple of the cells per byte, so the last byte is partial
+        let width = 11;
+        let mut row = vec![0; row_len(width)];
+        assert_eq!(row.len(), 3);
+
+        for x in 0..width {
+            set_cell_bits(&mut row, x, x as u8 % 4);
+        }
+        for x in 0..width {
+            assert_eq!(cell_bits(&row, x), x as u8 % 4);
+        }
+    }
+
+    #[test]
+    fn window_reads_back_the_file() {
+        let (width, height) = (9, 6);
+        let bits = |x: u32, y: u32| ((x * 7 + y * 3) % 4) as u8;
+
+        let mut maze = PackedMaze::new(width as usize, height as usize);
+        for y in 0..height {
+            for x in 0..width {
+                set_cell_bits(maze.row_mut(y as usize), x as usize, bits(x, y));
+            }
+        }
+
+        let mut bytes = Vec::new();
+        write_header(&mut bytes, width, height).unwrap();
+        bytes.extend_from_slice(&maze.bytes);
+
+        let mut file = MazeFile::open(Cursor::new(bytes)).unwrap();
+        assert_eq!((file.width(), file.height()), (width, height));
+
+        // Running past the maze, so it is clamped
+        let window = file
+            .read_window(UVec2::new(3, 2), UVec2::new(10, 3))
+            .unwrap();
+        assert_eq!((window.width(), window.height()), (6, 3));
+        for y in 0..3 {
+            for x in 0..6 {
+                assert_eq!(window.cell(UVec2::new(x, y)), bits(x + 3, y + 2));
+            }
+        }
+    }
+
+    #[test]
+    fn other_files_are_refused() {
+        let result = MazeFile::open(Cursor::new(b"PNG\0\0\0\0\0\0\0\0\0".to_vec()));
+        assert_eq!(result.err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+}
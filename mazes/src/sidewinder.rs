@@ -0,0 +1,109 @@
+use std::io::{self, Write};
+
+use rand::Rng;
+
+use crate::packed::{row_len, set_cell_bits, write_header, OPEN_DOWN, OPEN_RIGHT};
+
+/// Streams a maze generated with the Sidewinder algorithm into `writer`.
+///
+/// Every row only links to itself and the row below it, so rows are written
+/// as soon as they are carved and only one row is ever held in memory,
+/// whatever the height of the maze.
+pub fn generate<W: Write>(mut writer: W, width: u32, height: u32) -> io::Result<()> {
+    let mut rng = rand::rng();
+    let mut row = vec![0; row_len(width as usize)];
+
+    write_header(&mut writer, width, height)?;
+
+    for y in 0..height {
+        row.fill(0);
+        carve_row(&mut row, width as usize, y == 0, &mut rng);
+        writer.write_all(&row)?;
+    }
+
+    writer.flush()
+}
+
+fn carve_row<R: Rng>(row: &mut [u8], width: usize, first_row: bool, rng: &mut R) {
+    // The first row has nothing below it, so it is a single corridor
+    if first_row {
+        for x in 0..width - 1 {
+            set_cell_bits(row, x, OPEN_RIGHT);
+        }
+        return;
+    }
+
+    let mut run_start = 0;
+    for x in 0..width {
+        let at_east_border = x == width - 1;
+
+        if at_east_border || rng.random_bool(0.5) {
+            let passage = rng.random_range(run_start..=x);
+            set_cell_bits(row, passage, OPEN_DOWN);
+            run_start = x + 1;
+        } else {
+            set_cell_bits(row, x, OPEN_RIGHT);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use bevy::math::UVec2;
+
+    use super::*;
+    use crate::packed::MazeFile;
+
+    #[test]
+    fn streamed_maze_is_perfect() {
+        let (width, height) = (13, 9);
+        let mut bytes = Vec::new();
+        generate(&mut bytes, width, height).unwrap();
+
+        let mut file = MazeFile::open(Cursor::new(bytes)).unwrap();
+        let maze = file
+            .read_window(UVec2::ZERO, UVec2::new(width, height))
+            .unwrap();
+
+        // Passages, each recorded once by the cell left of or above it
+        let mut neighbours = vec![Vec::new(); (width * height) as usize];
+        let mut passages = 0;
+        for y in 0..height {
+            for x in 0..width {
+                let idx = (y * width + x) as usize;
+                let bits = maze.cell(UVec2::new(x, y));
+
+                if bits & OPEN_RIGHT != 0 {
+                    assert!(x + 1 < width, "passage out of the right side");
+                    neighbours[idx].push(idx + 1);
+                    neighbours[idx + 1].push(idx);
+                    passages += 1;
+                }
+                if bits & OPEN_DOWN != 0 {
+                    assert!(y > 0, "passage out of the bottom");
+                    neighbours[idx].push(idx - width as usize);
+                    neighbours[idx - width as usize].push(idx);
+                    passages += 1;
+                }
+            }
+        }
+
+        let mut reached = vec![false; neighbours.len()];
+        let mut stack = vec![0];
+        reached[0] = true;
+        while let Some(idx) = stack.pop() {
+            for &neighbour in &neighbours[idx] {
+                if !reached[neighbour] {
+                    reached[neighbour] = true;
+                    stack.push(neighbour);
+                }
+            }
+        }
+
+        // Connected with one passage less than cells, so without loops
+        assert!(reached.iter().all(|reached| *reached));
+        assert_eq!(passages, width * height - 1);
+    }
+}
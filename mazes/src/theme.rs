@@ -0,0 +1,308 @@
+use bevy::{
+    color::palettes,
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+use bevy_egui::egui::{self, Slider};
+
+use crate::{
+    camera::{FitMazeEvent, MazeBounds},
+    coord_to_idx,
+    dungeon::Dungeon,
+    idx_to_coord, reset_maze, Direction, Maze, Walls,
+};
+
+pub struct ThemePlugin;
+
+impl Plugin for ThemePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Theme>()
+            .add_systems(Startup, setup)
+            .add_systems(
+                Update,
+                apply_theme
+                    .after(reset_maze)
+                    .run_if(resource_changed::<Theme>),
+            );
+    }
+}
+
+/// Resolution of the texture used to draw cells with rounded corners.
+const CELL_IMAGE_SIZE: u32 = 64;
+
+#[derive(Resource, Clone, PartialEq)]
+pub struct Theme {
+    pub cell_size: f32,
+    pub wall_thickness: f32,
+    /// Radius of the cell corners, as a fraction of the cell size.
+    pub corner_radius: f32,
+    pub palette: Palette,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            cell_size: 32.0,
+            wall_thickness: 2.0,
+            corner_radius: 0.0,
+            palette: Palette::classic(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Palette {
+    pub background: Srgba,
+    pub unvisited: Srgba,
+    pub visited: Srgba,
+    pub current: Srgba,
+    pub wall: Srgba,
+    pub room: Srgba,
+    pub solid: Srgba,
+    pub stack: Srgba,
+    pub frontier: Srgba,
+}
+
+impl Palette {
+    pub fn classic() -> Self {
+        Self {
+            background: Srgba::rgb_u8(43, 44, 47),
+            unvisited: palettes::basic::AQUA,
+            visited: palettes::basic::FUCHSIA,
+            current: palettes::basic::BLUE,
+            wall: palettes::basic::BLACK,
+            room: palettes::basic::YELLOW,
+            solid: palettes::basic::BLACK,
+            stack: palettes::basic::RED,
+            frontier: palettes::basic::LIME,
+        }
+    }
+
+    /// Okabe-Ito colours, which stay distinguishable with the common forms of
+    /// colour blindness.
+    pub fn colour_blind_safe() -> Self {
+        Self {
+            background: Srgba::rgb_u8(43, 44, 47),
+            unvisited: Srgba::rgb_u8(86, 180, 233),
+            visited: Srgba::rgb_u8(230, 159, 0),
+            current: Srgba::rgb_u8(0, 114, 178),
+            wall: palettes::basic::BLACK,
+            room: Srgba::rgb_u8(240, 228, 66),
+            solid: palettes::basic::BLACK,
+            stack: Srgba::rgb_u8(213, 94, 0),
+            frontier: Srgba::rgb_u8(0, 158, 115),
+        }
+    }
+
+    /// Black walls on white, for printing.
+    pub fn print() -> Self {
+        Self {
+            background: palettes::basic::WHITE,
+            unvisited: palettes::basic::WHITE,
+            visited: palettes::basic::WHITE,
+            current: Srgba::gray(0.8),
+            wall: palettes::basic::BLACK,
+            room: palettes::basic::WHITE,
+            solid: palettes::basic::BLACK,
+            stack: Srgba::gray(0.3),
+            frontier: Srgba::gray(0.6),
+        }
+    }
+
+    pub fn cell_color(&self, maze: &Maze, dungeon: Option<&Dungeon>, coord: UVec2) -> Srgba {
+        let idx = coord_to_idx(coord, maze.width);
+
+        if let Some(dungeon) = dungeon {
+            if dungeon.is_solid(idx) {
+                return self.solid;
+            }
+            if dungeon.is_room(coord) {
+                return self.room;
+            }
+        }
+
+        if !maze.complete() && maze.stack.last() == Some(&coord) {
+            return self.current;
+        }
+
+        if maze.cells[idx].visited {
+            self.visited
+        } else {
+            self.unvisited
+        }
+    }
+}
+
+impl Theme {
+    /// Size of the wall sprite on the `direction` side of a cell, and its
+    /// offset from the cell center.
+    ///
+    /// Walls are one thickness longer than the cell so neighbouring walls
+    /// overlap at the corners instead of leaving a notch.
+    pub fn wall_layout(&self, direction: Direction) -> (Vec2, Vec2) {
+        let length = self.cell_size + self.wall_thickness;
+        let horizontal = Vec2::new(length, self.wall_thickness);
+        let vertical = Vec2::new(self.wall_thickness, length);
+
+        let offset = direction.to_coord().as_vec2() * self.cell_size / 2.0;
+
+        match direction {
+            Direction::Up | Direction::Down => (horizontal, offset),
+            Direction::Left | Direction::Right => (vertical, offset),
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.add(Slider::new(&mut self.cell_size, 4.0..=128.0).text("Cell size"));
+        ui.add(
+            Slider::new(&mut self.wall_thickness, 0.5..=self.cell_size / 2.0)
+                .text("Wall thickness"),
+        );
+        ui.add(Slider::new(&mut self.corner_radius, 0.0..=0.5).text("Corner radius"));
+
+        ui.horizontal(|ui| {
+            if ui.button("Classic").clicked() {
+                self.palette = Palette::classic();
+            }
+            if ui.button("Colour blind safe").clicked() {
+                self.palette = Palette::colour_blind_safe();
+            }
+            if ui.button("Print").clicked() {
+                self.palette = Palette::print();
+            }
+        });
+
+        let palette = &mut self.palette;
+        egui::Grid::new("Palette").show(ui, |ui| {
+            for (label, color) in [
+                ("Background", &mut palette.background),
+                ("Unvisited", &mut palette.unvisited),
+                ("Visited", &mut palette.visited),
+                ("Current", &mut palette.current),
+                ("Walls", &mut palette.wall),
+                ("Rooms", &mut palette.room),
+                ("Filled", &mut palette.solid),
+                ("Stack", &mut palette.stack),
+                ("Frontier", &mut palette.frontier),
+            ] {
+                ui.label(label);
+
+                let mut rgb = [color.red, color.green, color.blue];
+                if ui.color_edit_button_rgb(&mut rgb).changed() {
+                    *color = Srgba::rgb(rgb[0], rgb[1], rgb[2]);
+                }
+
+                ui.end_row();
+            }
+        });
+    }
+}
+
+/// White texture drawn by the cell sprites, its alpha gives them rounded corners.
+#[derive(Resource)]
+pub struct CellImage(pub Handle<Image>);
+
+fn setup(mut commands: Commands, mut images: ResMut<Assets<Image>>, theme: Res<Theme>) {
+    let image = images.add(rounded_square_image(theme.corner_radius));
+    commands.insert_resource(CellImage(image));
+}
+
+fn rounded_square_image(corner_radius: f32) -> Image {
+    let size = CELL_IMAGE_SIZE as f32;
+    let radius = corner_radius.clamp(0.0, 0.5) * size;
+    let half_size = Vec2::splat(size / 2.0);
+
+    let mut data = Vec::with_capacity((CELL_IMAGE_SIZE * CELL_IMAGE_SIZE * 4) as usize);
+    for y in 0..CELL_IMAGE_SIZE {
+        for x in 0..CELL_IMAGE_SIZE {
+            // Signed distance from the pixel center to the rounded square
+            let p = (Vec2::new(x as f32, y as f32) + 0.5 - half_size).abs();
+            let q = p - (half_size - radius);
+            let dist = q.max(Vec2::ZERO).length() + q.max_element().min(0.0) - radius;
+
+            let alpha = (0.5 - dist).clamp(0.0, 1.0);
+            data.extend_from_slice(&[255, 255, 255, (alpha * 255.0) as u8]);
+        }
+    }
+
+    Image::new(
+        Extent3d {
+            width: CELL_IMAGE_SIZE,
+            height: CELL_IMAGE_SIZE,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn apply_theme(
+    theme: Res<Theme>,
+    maze: Option<Res<Maze>>,
+    dungeon: Option<Res<Dungeon>>,
+    cell_image: Res<CellImage>,
+    mut images: ResMut<Assets<Image>>,
+    mut clear_color: ResMut<ClearColor>,
+    mut sprites: Query<(&mut Sprite, &mut Transform)>,
+    mut commands: Commands,
+    mut fit_event: EventWriter<FitMazeEvent>,
+    mut previous_cell_size: Local<Option<f32>>,
+) {
+    clear_color.0 = theme.palette.background.into();
+    images.insert(&cell_image.0, rounded_square_image(theme.corner_radius));
+
+    let cell_size_changed = previous_cell_size.replace(theme.cell_size) != Some(theme.cell_size);
+
+    let Some(maze) = maze else {
+        return;
+    };
+
+    for (idx, cell) in maze.cells.iter().enumerate() {
+        let coord = idx_to_coord(idx, maze.width);
+        let position = maze.cell_position(coord, theme.cell_size);
+
+        if let Ok((mut sprite, mut transform)) = sprites.get_mut(cell.entity) {
+            sprite.color = theme
+                .palette
+                .cell_color(&maze, dungeon.as_deref(), coord)
+                .into();
+            sprite.custom_size = Some(Vec2::splat(theme.cell_size));
+            transform.translation = Vec3::from((position, 0.0));
+        }
+
+        let Walls {
+            up,
+            down,
+            left,
+            right,
+        } = cell.walls;
+
+        for (direction, wall) in [
+            (Direction::Up, up),
+            (Direction::Down, down),
+            (Direction::Left, left),
+            (Direction::Right, right),
+        ] {
+            let Some(Ok((mut sprite, mut transform))) = wall.map(|wall| sprites.get_mut(wall))
+            else {
+                continue;
+            };
+
+            let (size, offset) = theme.wall_layout(direction);
+            sprite.color = theme.palette.wall.into();
+            sprite.custom_size = Some(size);
+            transform.translation = Vec3::from((position + offset, 1.0));
+        }
+    }
+
+    if cell_size_changed {
+        commands.insert_resource(MazeBounds(maze.bounds(theme.cell_size)));
+        fit_event.send_default();
+    }
+}
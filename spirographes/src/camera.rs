@@ -0,0 +1,195 @@
+use bevy::{
+    input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel},
+    prelude::*,
+    transform::TransformSystem,
+    window::PrimaryWindow,
+};
+use bevy_egui::EguiContexts;
+
+use crate::Spirographe;
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<FitDrawingEvent>()
+            .init_resource::<CameraConfig>()
+            .add_systems(Startup, setup)
+            .add_systems(
+                Update,
+                (
+                    handle_camera_input,
+                    control_camera,
+                    fit_drawing.run_if(on_event::<FitDrawingEvent>()),
+                )
+                    .chain(),
+            )
+            .add_systems(
+                PostUpdate,
+                follow_pencil
+                    .run_if(|config: Res<CameraConfig>| config.follow_pencil)
+                    .before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+const MIN_SCALE: f32 = 0.01;
+const MAX_SCALE: f32 = 100.0;
+/// Zoom factor of one notch of the mouse wheel.
+const ZOOM_STEP: f32 = 1.15;
+/// Pixels of a touchpad scroll worth one notch.
+const PIXELS_PER_LINE: f32 = 50.0;
+/// Space left around the drawing when fitting it, as a fraction of its size.
+const FIT_MARGIN: f32 = 0.05;
+
+/// Zooms and centres the camera on everything drawn.
+#[derive(Event, Default)]
+pub struct FitDrawingEvent;
+
+#[derive(Resource, Default)]
+pub struct CameraConfig {
+    /// Keeps the first pencil at the center of the view.
+    pub follow_pencil: bool,
+}
+
+fn setup(mut commands: Commands) {
+    // Camera
+    commands.spawn(Camera2dBundle::default());
+}
+
+fn handle_camera_input(
+    mut ctx: EguiContexts,
+    input: Res<ButtonInput<KeyCode>>,
+    mut config: ResMut<CameraConfig>,
+    mut fit_drawing_event: EventWriter<FitDrawingEvent>,
+) {
+    // Keys typed in the panel's text fields are theirs
+    if ctx.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+
+    if input.just_pressed(KeyCode::KeyF) {
+        fit_drawing_event.send_default();
+    }
+
+    if input.just_pressed(KeyCode::KeyP) {
+        config.follow_pencil = !config.follow_pencil;
+    }
+}
+
+/// Pans while a mouse button is held and zooms around the cursor, leaving
+/// the mouse to the panel when it's over it.
+#[allow(clippy::too_many_arguments)]
+fn control_camera(
+    mut ctx: EguiContexts,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    mut mouse_motion: EventReader<MouseMotion>,
+    mut wheel_scroll: EventReader<MouseWheel>,
+    mut camera_grabbed: Local<bool>,
+    mut config: ResMut<CameraConfig>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut camera: Query<(&mut Transform, &mut OrthographicProjection), With<Camera2d>>,
+) {
+    let Ok((mut camera, mut projection)) = camera.get_single_mut() else {
+        return;
+    };
+
+    let over_panel = ctx.ctx_mut().is_pointer_over_area();
+    let buttons = [MouseButton::Left, MouseButton::Middle];
+
+    if mouse_input.any_just_pressed(buttons) && !over_panel {
+        *camera_grabbed = true;
+    }
+    if !mouse_input.any_pressed(buttons) {
+        *camera_grabbed = false;
+    }
+
+    if *camera_grabbed {
+        for mouse_motion in mouse_motion.read() {
+            camera.translation.x -= mouse_motion.delta.x * projection.scale;
+            camera.translation.y += mouse_motion.delta.y * projection.scale;
+
+            // Dragging the view away from the pencil stops following it
+            if mouse_motion.delta != Vec2::ZERO && config.follow_pencil {
+                config.follow_pencil = false;
+            }
+        }
+    } else {
+        mouse_motion.clear();
+    }
+
+    if over_panel {
+        wheel_scroll.clear();
+        return;
+    }
+
+    let notches: f32 = wheel_scroll
+        .read()
+        .map(|scroll| match scroll.unit {
+            MouseScrollUnit::Line => scroll.y,
+            MouseScrollUnit::Pixel => scroll.y / PIXELS_PER_LINE,
+        })
+        .sum();
+    if notches == 0.0 {
+        return;
+    }
+
+    let scale = (projection.scale * ZOOM_STEP.powf(-notches)).clamp(MIN_SCALE, MAX_SCALE);
+
+    // Keep the point under the cursor where it is on the screen
+    let cursor_offset = windows.get_single().ok().and_then(|window| {
+        let cursor = window.cursor_position()?;
+        Some(Vec2::new(
+            cursor.x - window.width() / 2.0,
+            window.height() / 2.0 - cursor.y,
+        ))
+    });
+    if let Some(offset) = cursor_offset {
+        let shift = offset * (projection.scale - scale);
+        camera.translation += shift.extend(0.0);
+    }
+
+    projection.scale = scale;
+}
+
+fn fit_drawing(
+    spirographe: Res<Spirographe>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut camera: Query<(&mut Transform, &mut OrthographicProjection), With<Camera2d>>,
+) {
+    let (Ok((mut camera, mut projection)), Ok(window)) =
+        (camera.get_single_mut(), windows.get_single())
+    else {
+        return;
+    };
+
+    let mut bounds = Rect::EMPTY;
+    for point in spirographe.drawn_traces().flat_map(|trace| &trace.points) {
+        bounds = bounds.union_point(Vec2::new(point[0], point[1]));
+    }
+
+    // Nothing drawn yet, show the main circle
+    if bounds.is_empty() {
+        bounds = Rect::from_center_half_size(Vec2::ZERO, Vec2::splat(spirographe.ring_radius));
+    }
+
+    let size = bounds.size().max(Vec2::ONE) * (1.0 + 2.0 * FIT_MARGIN);
+    let window_size = Vec2::new(window.width(), window.height()).max(Vec2::ONE);
+
+    camera.translation = bounds.center().extend(camera.translation.z);
+    projection.scale = (size / window_size)
+        .max_element()
+        .clamp(MIN_SCALE, MAX_SCALE);
+}
+
+fn follow_pencil(spirographe: Res<Spirographe>, mut camera: Query<&mut Transform, With<Camera2d>>) {
+    let (Ok(mut camera), Some(pencil)) = (
+        camera.get_single_mut(),
+        spirographe.pencil_positions.first(),
+    ) else {
+        return;
+    };
+
+    camera.translation.x = pencil.x;
+    camera.translation.y = pencil.y;
+}
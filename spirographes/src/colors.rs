@@ -0,0 +1,172 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// World units of curve drawn for each pass through the palette, with a scale of 1.
+const ARC_LENGTH_CYCLE: f64 = 5000.0;
+/// Curvature, relative to the main circle's, reaching the end of the palette
+/// with a scale of 1.
+const CURVATURE_RANGE: f32 = 20.0;
+/// Laps around the main circle for each pass through the palette, with a scale of 1.
+const LAPS_PER_CYCLE: f32 = 12.0;
+
+/// What the colour of the trail follows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColorMode {
+    /// Hue cycling with how far the wheel rolled
+    Rainbow,
+    Solid,
+    /// Palette repeating along the length of the curve
+    ArcLength,
+    /// Palette from the straight parts to the tightest turns
+    Curvature,
+    /// Palette from the center to the main circle
+    Distance,
+    /// Palette stepping with each lap around the main circle
+    Lap,
+}
+
+impl ColorMode {
+    pub const ALL: [ColorMode; 6] = [
+        ColorMode::Rainbow,
+        ColorMode::Solid,
+        ColorMode::ArcLength,
+        ColorMode::Curvature,
+        ColorMode::Distance,
+        ColorMode::Lap,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorMode::Rainbow => "Rainbow",
+            ColorMode::Solid => "Solid",
+            ColorMode::ArcLength => "Arc length",
+            ColorMode::Curvature => "Curvature",
+            ColorMode::Distance => "Distance",
+            ColorMode::Lap => "Lap",
+        }
+    }
+}
+
+/// Colour at `position`, between 0 and 1, along a palette.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ColorStop {
+    pub position: f32,
+    /// sRGB colour.
+    pub color: [f32; 3],
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColorScheme {
+    pub mode: ColorMode,
+    /// Degrees added to the hue of the rainbow.
+    pub hue_offset: f32,
+    pub saturation: f32,
+    pub value: f32,
+    /// sRGB colour of the solid mode.
+    pub solid: [f32; 3],
+    /// Stops of the gradient the other modes pick from, sorted by position.
+    pub palette: Vec<ColorStop>,
+    /// Stretches what the palette is mapped to, higher going through it faster.
+    pub scale: f32,
+}
+
+impl Default for ColorScheme {
+    fn default() -> Self {
+        Self {
+            mode: ColorMode::Rainbow,
+            hue_offset: 0.0,
+            saturation: 1.0,
+            value: 1.0,
+            solid: [1.0, 1.0, 1.0],
+            palette: vec![
+                ColorStop {
+                    position: 0.0,
+                    color: [0.1, 0.2, 0.8],
+                },
+                ColorStop {
+                    position: 0.5,
+                    color: [0.9, 0.2, 0.6],
+                },
+                ColorStop {
+                    position: 1.0,
+                    color: [1.0, 0.8, 0.2],
+                },
+            ],
+            scale: 1.0,
+        }
+    }
+}
+
+/// Where the pencil is when a point is pushed, for the scheme to pick its colour.
+pub struct ColorSample {
+    pub t: f64,
+    pub position: Vec2,
+    /// Length of the curve drawn so far by the pencil.
+    pub arc_length: f64,
+    /// Inverse of the radius of the turn the pencil is taking.
+    pub curvature: f32,
+}
+
+impl ColorScheme {
+    pub fn color(&self, sample: &ColorSample, hue_speed: f32, ring_radius: f32) -> [f32; 4] {
+        let [r, g, b] = match self.mode {
+            ColorMode::Rainbow => {
+                let hue = (sample.t * hue_speed as f64 + self.hue_offset as f64).rem_euclid(360.0);
+                Color::hsv(hue as f32, self.saturation, self.value)
+                    .to_srgba()
+                    .to_f32_array_no_alpha()
+            }
+            ColorMode::Solid => self.solid,
+            ColorMode::ArcLength => {
+                let cycles = sample.arc_length / ARC_LENGTH_CYCLE * self.scale as f64;
+                self.sample_palette(cycles.fract() as f32)
+            }
+            ColorMode::Curvature => {
+                let relative = sample.curvature * ring_radius;
+                self.sample_palette(relative * self.scale / CURVATURE_RANGE)
+            }
+            ColorMode::Distance => {
+                let distance = sample.position.length() / ring_radius;
+                self.sample_palette(distance * self.scale)
+            }
+            ColorMode::Lap => {
+                let lap = (sample.t / std::f64::consts::TAU).floor() as f32;
+                self.sample_palette((lap * self.scale / LAPS_PER_CYCLE).fract())
+            }
+        };
+
+        [r, g, b, 1.0]
+    }
+
+    /// Keeps the stops in order after they were moved.
+    pub fn sort_palette(&mut self) {
+        self.palette
+            .sort_by(|a, b| a.position.total_cmp(&b.position));
+    }
+
+    /// Colour at `position` along the palette, clamped to its ends.
+    pub fn sample_palette(&self, position: f32) -> [f32; 3] {
+        let stops = &self.palette;
+
+        let (Some(first), Some(last)) = (stops.first(), stops.last()) else {
+            return self.solid;
+        };
+
+        if position <= first.position {
+            return first.color;
+        }
+
+        for pair in stops.windows(2) {
+            let (from, to) = (pair[0], pair[1]);
+            if position <= to.position {
+                let along =
+                    (position - from.position) / (to.position - from.position).max(f32::EPSILON);
+                return Vec3::from(from.color)
+                    .lerp(Vec3::from(to.color), along)
+                    .to_array();
+            }
+        }
+
+        last.color
+    }
+}
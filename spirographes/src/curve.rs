@@ -0,0 +1,396 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use bevy::math::{DVec2, Vec2};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    harmonograph::Harmonograph,
+    parametric::ParametricCurves,
+    shapes::{self, Outline},
+};
+
+pub const DEFAULT_RING_RADIUS: f32 = 500.0;
+
+/// The bar is as long as the ring is wide.
+pub fn bar_length(ring_radius: f32) -> f32 {
+    2.0 * ring_radius
+}
+
+/// Fixed shape the spinning circle rolls on, or what draws the curve
+/// instead of gears.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SpirographeMode {
+    /// Rolling inside the main circle
+    Hypotrochoid,
+    /// Rolling around the outside of the main circle
+    Epitrochoid,
+    /// Rolling all around a straight bar
+    Bar,
+    /// No gears, the pencils are swung by pendulums
+    Harmonograph,
+    Lissajous,
+    /// Rhodonea curve
+    Rose,
+    Superformula,
+    /// Lines between the points of a rose taken at a fixed angle apart
+    MaurerRose,
+    /// Chain of circles tracing a drawn path
+    Epicycles,
+}
+
+impl SpirographeMode {
+    pub const ALL: [SpirographeMode; 9] = [
+        SpirographeMode::Hypotrochoid,
+        SpirographeMode::Epitrochoid,
+        SpirographeMode::Bar,
+        SpirographeMode::Harmonograph,
+        SpirographeMode::Lissajous,
+        SpirographeMode::Rose,
+        SpirographeMode::Superformula,
+        SpirographeMode::MaurerRose,
+        SpirographeMode::Epicycles,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SpirographeMode::Hypotrochoid => "Inside",
+            SpirographeMode::Epitrochoid => "Outside",
+            SpirographeMode::Bar => "Bar",
+            SpirographeMode::Harmonograph => "Harmonograph",
+            SpirographeMode::Lissajous => "Lissajous",
+            SpirographeMode::Rose => "Rose",
+            SpirographeMode::Superformula => "Superformula",
+            SpirographeMode::MaurerRose => "Maurer rose",
+            SpirographeMode::Epicycles => "Epicycles",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let idx = Self::ALL.iter().position(|mode| *mode == self).unwrap_or(0);
+        Self::ALL[(idx + 1) % Self::ALL.len()]
+    }
+
+    /// Draws with wheels rolling on the ring or the bar.
+    pub fn has_gears(self) -> bool {
+        matches!(
+            self,
+            SpirographeMode::Hypotrochoid | SpirographeMode::Epitrochoid | SpirographeMode::Bar
+        )
+    }
+}
+
+/// Length of the parameter range after which a curve closes.
+#[derive(Debug, Clone, Copy)]
+pub struct Period {
+    /// Turns of the spinning circle around the main circle.
+    pub turns: u32,
+    /// Times the pencil swings out towards the main circle.
+    pub lobes: u32,
+}
+
+impl Period {
+    /// Period in terms of the `t` given to [`evaluate`].
+    pub fn length(&self) -> f64 {
+        self.turns as f64 * 2.0 * PI
+    }
+}
+
+/// Wheel of the chain, rolling on the ring, the bar or the wheel before it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Wheel {
+    pub radius: f32,
+    /// Rolls around the outside of what it rolls on, instead of inside.
+    pub outside: bool,
+    /// Goes around what it rolls on counterclockwise instead of clockwise.
+    pub reversed: bool,
+    /// Shape of the wheel when it isn't a circle, for a radius of 1.
+    pub outline: Option<Arc<Outline>>,
+    /// Shape of what it rolls on when it isn't a circle, for a radius of 1.
+    pub track: Option<Arc<Outline>>,
+}
+
+/// Where a wheel of the chain is at one point of the curve.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WheelPlacement {
+    pub center: Vec2,
+    pub angle: f32,
+}
+
+/// Pencil held in a hole of the last wheel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PencilMount {
+    /// Distance from the center of the wheel.
+    pub dist: f32,
+    /// Angle of the hole on the wheel, in radians.
+    pub phase: f32,
+}
+
+/// Wheels and pencils at one point of the curve.
+pub struct CurvePoint {
+    pub wheels: Vec<WheelPlacement>,
+    pub pencils: Vec<Vec2>,
+}
+
+/// Evaluates the curves drawn once the first wheel rolled `t` radians around
+/// the main circle, with the pencils on the last wheel of `wheels`.
+///
+/// The first wheel rolls clockwise, inside or outside the main circle as
+/// `mode` says, its own `outside` and `reversed` are ignored. On the bar, it
+/// covers the same distance as it would on the main circle. Every other wheel
+/// goes around the one before it by `t` radians, measured from that wheel.
+/// Around gears that aren't circles, a radian stands for as much of their
+/// outline as it does on a circle as long.
+/// Everything is computed in `f64` since `t` keeps growing for as long as the
+/// curve is drawn.
+///
+/// The harmonograph and the parametric curves have no wheels, they place the
+/// pencils themselves, only looking at their phase.
+pub fn evaluate(
+    mode: SpirographeMode,
+    ring_radius: f32,
+    wheels: &[Wheel],
+    pencils: &[PencilMount],
+    harmonograph: &Harmonograph,
+    parametric: &ParametricCurves,
+    t: f64,
+) -> CurvePoint {
+    if !mode.has_gears() {
+        return CurvePoint {
+            wheels: Vec::new(),
+            pencils: pencil_positions(
+                mode,
+                ring_radius,
+                wheels,
+                pencils,
+                harmonograph,
+                parametric,
+                t,
+            )
+            .collect(),
+        };
+    }
+
+    let mut placements = Vec::with_capacity(wheels.len());
+    let (center, rotation) = roll(mode, ring_radius, wheels, t, |center, rotation| {
+        placements.push(WheelPlacement {
+            center: center.as_vec2(),
+            angle: rotation.rem_euclid(2.0 * PI) as f32,
+        })
+    });
+
+    CurvePoint {
+        wheels: placements,
+        pencils: pencils
+            .iter()
+            .map(|pencil| place_pencil(center, rotation, pencil))
+            .collect(),
+    }
+}
+
+/// Like [`evaluate`], for when only the pencils are needed.
+pub fn pencil_positions<'a>(
+    mode: SpirographeMode,
+    ring_radius: f32,
+    wheels: &[Wheel],
+    pencils: &'a [PencilMount],
+    harmonograph: &'a Harmonograph,
+    parametric: &'a ParametricCurves,
+    t: f64,
+) -> impl Iterator<Item = Vec2> + 'a {
+    let rolled = mode
+        .has_gears()
+        .then(|| roll(mode, ring_radius, wheels, t, |_, _| {}));
+
+    pencils.iter().map(move |pencil| match rolled {
+        Some((center, rotation)) => place_pencil(center, rotation, pencil),
+        None if mode == SpirographeMode::Harmonograph => {
+            harmonograph.position(ring_radius, pencil.phase, t)
+        }
+        None => parametric.position(mode, ring_radius, pencil.phase, t),
+    })
+}
+
+fn place_pencil(center: DVec2, rotation: f64, pencil: &PencilMount) -> Vec2 {
+    (center + DVec2::from_angle(rotation + pencil.phase as f64) * pencil.dist as f64).as_vec2()
+}
+
+/// Rolls every wheel of the chain in turn, giving the center and rotation of
+/// each to `place`, and returns the center and rotation of the last one.
+fn roll(
+    mode: SpirographeMode,
+    ring_radius: f32,
+    wheels: &[Wheel],
+    t: f64,
+    mut place: impl FnMut(DVec2, f64),
+) -> (DVec2, f64) {
+    let main_radius = ring_radius as f64;
+
+    let mut center = DVec2::ZERO;
+    let mut rotation = 0.0;
+    let mut parent_radius = main_radius;
+
+    for (idx, wheel) in wheels.iter().enumerate() {
+        let radius = wheel.radius as f64;
+
+        (center, rotation) = match (idx, mode) {
+            (0, SpirographeMode::Bar) => {
+                let rotation = t * main_radius / radius;
+                let bar_length = bar_length(ring_radius) as f64;
+                (bar_circle_position(rotation, radius, bar_length), -rotation)
+            }
+            _ => {
+                // The first wheel goes clockwise around the ring, the others
+                // the same way in the frame of the wheel they roll on
+                let (outside, orbit) = match idx {
+                    0 => (mode == SpirographeMode::Epitrochoid, -t),
+                    _ => (wheel.outside, if wheel.reversed { t } else { -t }),
+                };
+                let (relative_center, relative_rotation) = shapes::roll_on(
+                    wheel.track.as_deref(),
+                    parent_radius,
+                    wheel.outline.as_deref(),
+                    radius,
+                    outside,
+                    orbit,
+                );
+
+                (
+                    center + DVec2::from_angle(rotation).rotate(relative_center),
+                    rotation + relative_rotation,
+                )
+            }
+        };
+
+        parent_radius = radius;
+        place(center, rotation);
+    }
+
+    (center, rotation)
+}
+
+/// Center of a circle of `radius` that turned `rotation` radians clockwise
+/// while rolling around the bar, starting on top of its left end.
+///
+/// Along the sides the circle advances by its radius per radian, around the
+/// ends it pivots on the end point for half a turn.
+fn bar_circle_position(rotation: f64, radius: f64, bar_length: f64) -> DVec2 {
+    let half_length = bar_length / 2.0;
+    let side = bar_length / radius;
+    let period = 2.0 * side + 2.0 * PI;
+
+    let rotation = rotation.rem_euclid(period);
+
+    if rotation < side {
+        DVec2::new(-half_length + rotation * radius, radius)
+    } else if rotation < side + PI {
+        let a = rotation - side;
+        DVec2::new(half_length, 0.0) + DVec2::new(f64::sin(a), f64::cos(a)) * radius
+    } else if rotation < 2.0 * side + PI {
+        DVec2::new(half_length - (rotation - side - PI) * radius, -radius)
+    } else {
+        let a = rotation - 2.0 * side - PI;
+        DVec2::new(-half_length, 0.0) - DVec2::new(f64::sin(a), f64::cos(a)) * radius
+    }
+}
+
+/// Computes after how long the curve closes on itself.
+///
+/// `teeth` lists the ring followed by every wheel of the chain. A wheel is
+/// back at its starting point on the gear it rolls on once it rolled a whole
+/// number of its own teeth and of that gear's, i.e. after `wheel / gcd(gear, wheel)`
+/// turns. The curve closes once every wheel is, after the least common
+/// multiple of those. The first wheel draws `ring / gcd(ring, wheel)` lobes
+/// every `wheel / gcd(ring, wheel)` turns. The bar's ends add half turns that are not a
+/// rational part of its length, so curves on it never close. Curves drawn
+/// without gears have their own period.
+pub fn period(mode: SpirographeMode, teeth: &[u32]) -> Option<Period> {
+    if mode == SpirographeMode::Bar || !mode.has_gears() || teeth.len() < 2 || teeth.contains(&0) {
+        return None;
+    }
+
+    // Long chains can need more turns than fit, which is as good as never closing
+    let turns = teeth
+        .windows(2)
+        .map(|pair| pair[1] / gcd(pair[0], pair[1]))
+        .try_fold(1, lcm)?;
+
+    let divisor = gcd(teeth[0], teeth[1]);
+    let lobes = (turns / (teeth[1] / divisor)).checked_mul(teeth[0] / divisor)?;
+
+    Some(Period { turns, lobes })
+}
+
+pub fn gcd(mut a: u32, mut b: u32) -> u32 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+fn lcm(a: u32, b: u32) -> Option<u32> {
+    (a / gcd(a, b)).checked_mul(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn turns_and_lobes(mode: SpirographeMode, teeth: &[u32]) -> Option<(u32, u32)> {
+        period(mode, teeth).map(|period| (period.turns, period.lobes))
+    }
+
+    #[test]
+    fn gcd_and_lcm() {
+        assert_eq!(gcd(12, 18), 6);
+        assert_eq!(gcd(18, 12), 6);
+        assert_eq!(gcd(7, 0), 7);
+        assert_eq!(gcd(105, 32), 1);
+
+        assert_eq!(lcm(4, 6), Some(12));
+        assert_eq!(lcm(5, 5), Some(5));
+        assert_eq!(lcm(u32::MAX, 2), None);
+    }
+
+    #[test]
+    fn single_wheel_period() {
+        // The wheel is back after 36 / 12 turns, having drawn 96 / 12 lobes
+        assert_eq!(
+            turns_and_lobes(SpirographeMode::Hypotrochoid, &[96, 36]),
+            Some((3, 8))
+        );
+        assert_eq!(
+            turns_and_lobes(SpirographeMode::Epitrochoid, &[105, 32]),
+            Some((32, 105))
+        );
+        assert_eq!(
+            turns_and_lobes(SpirographeMode::Hypotrochoid, &[60, 60]),
+            Some((1, 1))
+        );
+    }
+
+    #[test]
+    fn chain_period() {
+        // The wheels close after 3 and 5 turns, so the curve after 15
+        assert_eq!(
+            turns_and_lobes(SpirographeMode::Hypotrochoid, &[96, 36, 20]),
+            Some((15, 40))
+        );
+        // The last wheel closes every turn of the first one
+        assert_eq!(
+            turns_and_lobes(SpirographeMode::Hypotrochoid, &[96, 36, 12]),
+            Some((3, 8))
+        );
+    }
+
+    #[test]
+    fn curves_that_never_close() {
+        assert!(period(SpirographeMode::Bar, &[96, 36]).is_none());
+        assert!(period(SpirographeMode::Harmonograph, &[96, 36]).is_none());
+        assert!(period(SpirographeMode::Hypotrochoid, &[96]).is_none());
+        assert!(period(SpirographeMode::Hypotrochoid, &[96, 0]).is_none());
+
+        // Coprime wheels whose turns overflow
+        let primes = [7919, 7907, 7901, 7883, 7877];
+        assert!(period(SpirographeMode::Hypotrochoid, &primes).is_none());
+    }
+}
@@ -0,0 +1,353 @@
+use std::{
+    fmt::Write as _,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+};
+
+use bevy::{
+    prelude::*,
+    tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task},
+};
+
+use crate::{Spirographe, Trace};
+
+pub struct ExportPlugin;
+
+impl Plugin for ExportPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ExportSvgEvent>()
+            .add_event::<ExportPngEvent>()
+            .init_resource::<ExportConfig>()
+            .add_systems(
+                Update,
+                (
+                    export_svg.run_if(on_event::<ExportSvgEvent>()),
+                    start_png_export.run_if(on_event::<ExportPngEvent>()),
+                    poll_png_export.run_if(resource_exists::<PngExportTask>),
+                ),
+            );
+    }
+}
+
+/// Space left around the curve, as a fraction of its size.
+const MARGIN: f32 = 0.05;
+/// Largest PNG offered in the panel. Only a band of rows is held in memory
+/// while writing, so this bounds the file size rather than the memory.
+pub const MAX_PNG_SIZE: u32 = 65536;
+/// Rows of the PNG blended at once.
+const BAND_ROWS: u32 = 64;
+
+#[derive(Event, Default)]
+pub struct ExportSvgEvent;
+
+#[derive(Event, Default)]
+pub struct ExportPngEvent;
+
+#[derive(Resource)]
+pub struct ExportConfig {
+    pub svg_path: String,
+    pub png_path: String,
+    /// Width and height of the PNG, in pixels.
+    pub png_size: u32,
+    /// Width of the line, in pixels in the PNG and in world units in the SVG.
+    pub line_width: f32,
+    pub transparent_background: bool,
+    pub status: String,
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        Self {
+            svg_path: String::from("spirographe.svg"),
+            png_path: String::from("spirographe.png"),
+            png_size: 4096,
+            line_width: 2.0,
+            transparent_background: false,
+            status: String::new(),
+        }
+    }
+}
+
+#[derive(Resource)]
+struct PngExportTask(Task<io::Result<()>>);
+
+/// Smallest square holding every point of every trace, with the margin around it.
+fn drawing_bounds(traces: &[Trace]) -> Rect {
+    let mut bounds = Rect::EMPTY;
+    for point in traces.iter().flat_map(|trace| &trace.points) {
+        bounds = bounds.union_point(Vec2::new(point[0], point[1]));
+    }
+
+    let size = bounds.size().max_element().max(1.0) * (1.0 + 2.0 * MARGIN);
+    Rect::from_center_size(bounds.center(), Vec2::splat(size))
+}
+
+fn to_srgb_u8(color: [f32; 4]) -> [u8; 4] {
+    color.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8)
+}
+
+fn svg_color(color: [f32; 4]) -> String {
+    let [r, g, b, _] = to_srgb_u8(color);
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+/// Writes the curves as polylines, a new one starting whenever the colour
+/// changes, one pen after the other.
+fn write_svg(
+    path: &str,
+    traces: &[Trace],
+    background: Option<[f32; 4]>,
+    line_width: f32,
+) -> io::Result<()> {
+    let bounds = drawing_bounds(traces);
+    let mut svg = String::new();
+
+    // The y axis points down in SVG, the viewBox is flipped to match
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}">"#,
+        bounds.min.x,
+        -bounds.max.y,
+        bounds.width(),
+        bounds.height()
+    );
+
+    if let Some(background) = background {
+        let _ = writeln!(
+            svg,
+            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}"/>"#,
+            bounds.min.x,
+            -bounds.max.y,
+            bounds.width(),
+            bounds.height(),
+            svg_color(background)
+        );
+    }
+
+    let _ = writeln!(
+        svg,
+        r#"<g fill="none" stroke-width="{line_width}" stroke-linecap="round" stroke-linejoin="round">"#
+    );
+
+    for Trace { points, colors, .. } in traces {
+        let mut start = 0;
+        while start + 1 < points.len() {
+            let color = svg_color(colors[start + 1]);
+
+            // Each segment takes the colour of its end point
+            let mut end = start + 1;
+            while end + 1 < points.len() && svg_color(colors[end + 1]) == color {
+                end += 1;
+            }
+
+            let coords = points[start..=end]
+                .iter()
+                .map(|point| format!("{},{}", point[0], -point[1]))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let _ = writeln!(svg, r#"<polyline stroke="{color}" points="{coords}"/>"#);
+
+            start = end;
+        }
+    }
+
+    svg.push_str("</g>\n</svg>\n");
+
+    fs::write(path, svg)
+}
+
+/// Rasterizes the curves with anti-aliased lines of `line_width` pixels and
+/// writes them to `path`.
+///
+/// The image is blended and compressed a band of rows at a time, so no
+/// more than one band is ever held in memory, whatever the size.
+fn write_png(
+    path: &str,
+    traces: &[Trace],
+    background: Option<[f32; 4]>,
+    size: u32,
+    line_width: f32,
+) -> io::Result<()> {
+    let bounds = drawing_bounds(traces);
+    let scale = size as f32 / bounds.width();
+    let to_pixel = |point: &[f32; 3]| {
+        Vec2::new(
+            (point[0] - bounds.min.x) * scale,
+            (bounds.max.y - point[1]) * scale,
+        )
+    };
+
+    let half_width = line_width / 2.0;
+    let reach = half_width + 1.0;
+    let band_count = size.div_ceil(BAND_ROWS) as usize;
+
+    // Every segment, listed in each band it reaches into, in drawing order
+    let segments: Vec<(Vec2, Vec2, [f32; 4])> = traces
+        .iter()
+        .flat_map(|trace| trace.points.windows(2).zip(trace.colors.iter().skip(1)))
+        .map(|(segment, color)| (to_pixel(&segment[0]), to_pixel(&segment[1]), *color))
+        .collect();
+    let mut bands: Vec<Vec<u32>> = vec![Vec::new(); band_count];
+    for (idx, (a, b, _)) in segments.iter().enumerate() {
+        let top = ((a.y.min(b.y) - reach).max(0.0) as u32 / BAND_ROWS) as usize;
+        let bottom = ((a.y.max(b.y) + reach).max(0.0) as u32 / BAND_ROWS) as usize;
+        for band in &mut bands[top.min(band_count)..(bottom + 1).min(band_count)] {
+            band.push(idx as u32);
+        }
+    }
+
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), size, size);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?.into_stream_writer()?;
+
+    let mut pixels: Vec<u8> = Vec::with_capacity((size * BAND_ROWS * 4) as usize);
+    let mut canvas = Vec::with_capacity((size * BAND_ROWS) as usize);
+
+    for (band, listed) in bands.iter().enumerate() {
+        let first_row = band as u32 * BAND_ROWS;
+        let rows = BAND_ROWS.min(size - first_row);
+        canvas.clear();
+        canvas.resize((size * rows) as usize, background.unwrap_or([0.0; 4]));
+
+        for &(a, b, color) in listed.iter().map(|idx| &segments[*idx as usize]) {
+            let min = (a.min(b) - reach).max(Vec2::new(0.0, first_row as f32));
+            let max = (a.max(b) + reach).min(Vec2::new(
+                size as f32 - 1.0,
+                (first_row + rows) as f32 - 1.0,
+            ));
+            if min.x > max.x || min.y > max.y {
+                continue;
+            }
+            let (min, max) = (min.as_uvec2(), max.as_uvec2());
+
+            for y in min.y..=max.y {
+                for x in min.x..=max.x {
+                    let pixel = Vec2::new(x as f32, y as f32) + 0.5;
+                    let coverage =
+                        (half_width + 0.5 - segment_distance(pixel, a, b)).clamp(0.0, 1.0);
+                    if coverage <= 0.0 {
+                        continue;
+                    }
+
+                    // Source over, with straight alpha so transparent backgrounds
+                    // don't darken the edges
+                    let dest = &mut canvas[((y - first_row) * size + x) as usize];
+                    let alpha = coverage * color[3];
+                    let dest_alpha = dest[3] * (1.0 - alpha);
+                    let out_alpha = alpha + dest_alpha;
+                    for channel in 0..3 {
+                        dest[channel] =
+                            (color[channel] * alpha + dest[channel] * dest_alpha) / out_alpha;
+                    }
+                    dest[3] = out_alpha;
+                }
+            }
+        }
+
+        pixels.clear();
+        pixels.extend(canvas.iter().copied().flat_map(to_srgb_u8));
+        writer.write_all(&pixels)?;
+    }
+
+    Ok(writer.finish()?)
+}
+
+fn segment_distance(point: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let ab = b - a;
+    let t = ((point - a).dot(ab) / ab.length_squared().max(f32::EPSILON)).clamp(0.0, 1.0);
+    point.distance(a + ab * t)
+}
+
+fn background_color(config: &ExportConfig, clear_color: &ClearColor) -> Option<[f32; 4]> {
+    (!config.transparent_background).then(|| clear_color.0.to_srgba().to_f32_array())
+}
+
+fn export_svg(
+    spirographe: Res<Spirographe>,
+    clear_color: Res<ClearColor>,
+    mut config: ResMut<ExportConfig>,
+) {
+    let result = write_svg(
+        &config.svg_path,
+        &spirographe.drawn_traces().cloned().collect::<Vec<_>>(),
+        background_color(&config, &clear_color),
+        config.line_width,
+    );
+
+    config.status = match result {
+        Ok(()) => format!("Exported {}", config.svg_path),
+        Err(e) => format!("Export failed: {e}"),
+    };
+}
+
+fn start_png_export(
+    mut commands: Commands,
+    spirographe: Res<Spirographe>,
+    clear_color: Res<ClearColor>,
+    mut config: ResMut<ExportConfig>,
+    task: Option<Res<PngExportTask>>,
+) {
+    if task.is_some() {
+        return;
+    }
+
+    let traces: Vec<Trace> = spirographe.drawn_traces().cloned().collect();
+    let background = background_color(&config, &clear_color);
+    let size = config.png_size.max(1);
+    let line_width = config.line_width;
+    let path = config.png_path.clone();
+
+    let task = AsyncComputeTaskPool::get()
+        .spawn(async move { write_png(&path, &traces, background, size, line_width) });
+
+    config.status = format!("Rendering {size}x{size} PNG...");
+    commands.insert_resource(PngExportTask(task));
+}
+
+fn poll_png_export(
+    mut commands: Commands,
+    mut task: ResMut<PngExportTask>,
+    mut config: ResMut<ExportConfig>,
+) {
+    let Some(result) = block_on(future::poll_once(&mut task.0)) else {
+        return;
+    };
+
+    commands.remove_resource::<PngExportTask>();
+
+    config.status = match result {
+        Ok(()) => format!("Exported {}", config.png_path),
+        Err(e) => format!("Export failed: {e}"),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn png_is_streamed_whole() {
+        let trace = Trace {
+            points: vec![[0.0, 0.0, 0.0], [10.0, 5.0, 0.0], [3.0, 9.0, 0.0]],
+            colors: vec![[1.0, 0.0, 0.0, 1.0]; 3],
+            ..default()
+        };
+        // Not a multiple of the band height, so the last band is partial
+        let size = BAND_ROWS * 2 + 7;
+        let path = std::env::temp_dir().join("spirographes_export_test.png");
+        let path = path.to_str().unwrap();
+
+        write_png(path, &[trace], Some([1.0; 4]), size, 2.0).unwrap();
+
+        let decoder = png::Decoder::new(io::BufReader::new(File::open(path).unwrap()));
+        let mut reader = decoder.read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size().unwrap()];
+        reader.next_frame(&mut pixels).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!((reader.info().width, reader.info().height), (size, size));
+        assert!(pixels.chunks(4).any(|pixel| pixel == [255, 255, 255, 255]));
+        assert!(pixels.chunks(4).any(|pixel| pixel[1] < 128));
+    }
+}
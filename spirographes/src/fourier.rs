@@ -0,0 +1,315 @@
+use std::{f64::consts::TAU, fs, io};
+
+use bevy::{math::DVec2, prelude::*, window::PrimaryWindow};
+use bevy_egui::EguiContexts;
+use serde::{Deserialize, Serialize};
+
+use crate::{curve::SpirographeMode, svg_path, Spirographe};
+
+pub struct FourierPlugin;
+
+impl Plugin for FourierPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<LoadSvgPathEvent>()
+            .init_resource::<FourierConfig>()
+            .add_systems(
+                Update,
+                (
+                    draw_path,
+                    load_svg_path.run_if(on_event::<LoadSvgPathEvent>()),
+                    draw_epicycles.run_if(|config: Res<FourierConfig>| config.show_epicycles),
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Points the path is resampled to, and so most epicycles kept.
+const PATH_SAMPLES: usize = 512;
+/// Circles a new path is first drawn with.
+const DEFAULT_KEPT: usize = 100;
+/// Screen pixels between two recorded points of a freehand path.
+const DRAWN_POINT_SPACING: f32 = 3.0;
+/// Part of the ring a loaded path is scaled to fit in.
+const LOADED_PATH_SIZE: f32 = 0.9;
+
+#[derive(Event, Default)]
+pub struct LoadSvgPathEvent;
+
+#[derive(Resource)]
+pub struct FourierConfig {
+    pub show_epicycles: bool,
+    /// SVG file whose first path is loaded.
+    pub svg_path: String,
+    pub status: String,
+}
+
+impl Default for FourierConfig {
+    fn default() -> Self {
+        Self {
+            show_epicycles: true,
+            svg_path: String::from("path.svg"),
+            status: String::new(),
+        }
+    }
+}
+
+/// Circle turning `frequency` times per turn of `t`, carrying the next one on its edge.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Epicycle {
+    pub frequency: i32,
+    /// Fraction of the ring radius.
+    pub radius: f32,
+    /// In radians.
+    pub phase: f32,
+}
+
+impl Epicycle {
+    fn arm(&self, t: f64) -> DVec2 {
+        DVec2::from_angle(self.frequency as f64 * t + self.phase as f64) * self.radius as f64
+    }
+}
+
+/// Fourier series of a closed path, as a chain of circles.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Epicycles {
+    /// From the largest to the smallest.
+    pub circles: Vec<Epicycle>,
+    /// Circles drawn with, the smaller ones left out.
+    pub kept: usize,
+}
+
+impl Default for Epicycles {
+    fn default() -> Self {
+        let circle = |frequency, radius| Epicycle {
+            frequency,
+            radius,
+            phase: 0.0,
+        };
+
+        Self {
+            circles: vec![circle(1, 0.6), circle(-4, 0.25), circle(6, 0.1)],
+            kept: 3,
+        }
+    }
+}
+
+impl Epicycles {
+    /// Takes the discrete Fourier transform of the closed path through
+    /// `points`, resampled at even distances along it.
+    pub fn from_path(points: &[Vec2]) -> Option<Self> {
+        let samples = resample_closed(points, PATH_SAMPLES)?;
+        let count = samples.len() as i32;
+
+        let mut circles: Vec<Epicycle> = (-count / 2..count - count / 2)
+            .map(|frequency| {
+                let sum: DVec2 = samples
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, sample)| {
+                        let angle = -TAU * frequency as f64 * idx as f64 / count as f64;
+                        DVec2::from_angle(angle).rotate(sample.as_dvec2())
+                    })
+                    .sum();
+                let coefficient = sum / count as f64;
+
+                Epicycle {
+                    frequency,
+                    radius: coefficient.length() as f32,
+                    phase: coefficient.to_angle() as f32,
+                }
+            })
+            .collect();
+        circles.sort_by(|a, b| b.radius.total_cmp(&a.radius));
+
+        Some(Self {
+            kept: DEFAULT_KEPT.min(circles.len()),
+            circles,
+        })
+    }
+
+    fn kept(&self) -> &[Epicycle] {
+        &self.circles[..self.kept.min(self.circles.len())]
+    }
+
+    pub fn position(&self, t: f64) -> DVec2 {
+        self.kept().iter().map(|circle| circle.arm(t)).sum()
+    }
+
+    /// Center of every kept circle at `t`, then the pencil.
+    fn centers(&self, t: f64) -> impl Iterator<Item = DVec2> + '_ {
+        self.kept().iter().scan(DVec2::ZERO, move |center, circle| {
+            let previous = *center;
+            *center += circle.arm(t);
+            Some(previous)
+        })
+    }
+}
+
+/// `count` points at even distances along the path, closed back on its first point.
+fn resample_closed(points: &[Vec2], count: usize) -> Option<Vec<Vec2>> {
+    let closed: Vec<Vec2> = points.iter().chain(points.first()).copied().collect();
+    let lengths: Vec<f32> = closed
+        .windows(2)
+        .scan(0.0, |length, pair| {
+            *length += pair[0].distance(pair[1]);
+            Some(*length)
+        })
+        .collect();
+
+    let total = *lengths.last()?;
+    if total <= f32::EPSILON {
+        return None;
+    }
+
+    let mut segment = 0;
+    let samples = (0..count)
+        .map(|idx| {
+            let along = total * idx as f32 / count as f32;
+            while lengths[segment] < along {
+                segment += 1;
+            }
+
+            let start = if segment == 0 {
+                0.0
+            } else {
+                lengths[segment - 1]
+            };
+            let fraction = (along - start) / (lengths[segment] - start).max(f32::EPSILON);
+            closed[segment].lerp(closed[segment + 1], fraction)
+        })
+        .collect();
+
+    Some(samples)
+}
+
+/// Replaces the epicycles with the ones of a new path, given in ring radii.
+fn apply_path(spirographe: &mut Spirographe, points: &[Vec2]) -> bool {
+    let mut parametric = spirographe.parametric.clone();
+    let Some(epicycles) = Epicycles::from_path(points) else {
+        return false;
+    };
+    parametric.epicycles = epicycles;
+
+    if spirographe.mode != SpirographeMode::Epicycles {
+        spirographe.set_mode(SpirographeMode::Epicycles);
+    }
+    spirographe.set_parametric(parametric);
+
+    true
+}
+
+/// Records a freehand path while the right button is held, turning it into
+/// epicycles once it's released.
+fn draw_path(
+    mut ctx: EguiContexts,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform, &OrthographicProjection)>,
+    mut spirographe: ResMut<Spirographe>,
+    mut path: Local<Option<Vec<Vec2>>>,
+    mut gizmos: Gizmos,
+) {
+    if spirographe.mode != SpirographeMode::Epicycles {
+        return;
+    }
+
+    if mouse_input.just_pressed(MouseButton::Right) && !ctx.ctx_mut().is_pointer_over_area() {
+        *path = Some(Vec::new());
+    }
+
+    let Some(points) = path.as_mut() else {
+        return;
+    };
+
+    let Ok((camera, transform, projection)) = camera.get_single() else {
+        return;
+    };
+
+    let cursor = windows
+        .get_single()
+        .ok()
+        .and_then(Window::cursor_position)
+        .and_then(|cursor| camera.viewport_to_world_2d(transform, cursor));
+
+    if let Some(cursor) = cursor {
+        let spacing = DRAWN_POINT_SPACING * projection.scale;
+        if points
+            .last()
+            .is_none_or(|last| last.distance(cursor) >= spacing)
+        {
+            points.push(cursor);
+        }
+    }
+
+    gizmos.linestrip_2d(points.iter().copied(), Color::srgb(0.6, 0.6, 0.6));
+
+    if !mouse_input.just_released(MouseButton::Right) {
+        return;
+    }
+
+    let ring_radius = spirographe.ring_radius;
+    let in_rings: Vec<Vec2> = points.iter().map(|point| *point / ring_radius).collect();
+    apply_path(&mut spirographe, &in_rings);
+
+    *path = None;
+}
+
+fn read_svg_path(path: &str) -> io::Result<Vec<Vec2>> {
+    let content = fs::read_to_string(path)?;
+    let data = svg_path::first_path_data(&content)
+        .ok_or_else(|| io::Error::other("no path in the file"))?;
+
+    svg_path::flatten(data).map_err(io::Error::other)
+}
+
+/// Scales the path to fit in the ring, centered on it.
+fn fit_in_ring(points: &[Vec2]) -> Vec<Vec2> {
+    let mut bounds = Rect::EMPTY;
+    for point in points {
+        bounds = bounds.union_point(*point);
+    }
+
+    let scale = 2.0 * LOADED_PATH_SIZE / bounds.size().max_element().max(f32::EPSILON);
+    points
+        .iter()
+        .map(|point| (*point - bounds.center()) * scale)
+        .collect()
+}
+
+fn load_svg_path(mut spirographe: ResMut<Spirographe>, mut config: ResMut<FourierConfig>) {
+    config.status = match read_svg_path(&config.svg_path) {
+        Ok(points) if apply_path(&mut spirographe, &fit_in_ring(&points)) => {
+            format!("Loaded {}", config.svg_path)
+        }
+        Ok(_) => String::from("Load failed: the path has no length"),
+        Err(e) => format!("Load failed: {e}"),
+    };
+}
+
+/// Draws the chain of circles carrying the first pencil.
+fn draw_epicycles(spirographe: Res<Spirographe>, mut gizmos: Gizmos) {
+    if spirographe.mode != SpirographeMode::Epicycles {
+        return;
+    }
+
+    let epicycles = &spirographe.parametric.epicycles;
+    let ring_radius = spirographe.ring_radius as f64;
+    let phase = spirographe
+        .pencil_mounts
+        .first()
+        .map_or(0.0, |pencil| pencil.phase as f64);
+    let turn = |point: DVec2| (DVec2::from_angle(phase).rotate(point) * ring_radius).as_vec2();
+
+    let color = Color::srgba(1.0, 1.0, 1.0, 0.4);
+    let centers: Vec<Vec2> = epicycles
+        .centers(spirographe.t)
+        .map(turn)
+        .chain(std::iter::once(turn(epicycles.position(spirographe.t))))
+        .collect();
+
+    for (center, circle) in centers.iter().zip(epicycles.kept()) {
+        gizmos.circle_2d(*center, circle.radius * ring_radius as f32, color);
+    }
+    gizmos.linestrip_2d(centers, color);
+}
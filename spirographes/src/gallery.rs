@@ -0,0 +1,408 @@
+use std::{f64::consts::TAU, ops::RangeInclusive};
+
+use bevy::{
+    prelude::*,
+    tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task},
+};
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    curve::{SpirographeMode, DEFAULT_RING_RADIUS},
+    gears::{Gears, MIN_TEETH},
+    presets::{ApplyDesignEvent, Design},
+    Spirographe, Trace,
+};
+
+pub struct GalleryPlugin;
+
+impl Plugin for GalleryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GalleryConfig>()
+            .init_resource::<Thumbnails>()
+            .add_systems(
+                Update,
+                (update_thumbnails, show_gallery)
+                    .chain()
+                    .run_if(|config: Res<GalleryConfig>| config.open),
+            );
+    }
+}
+
+/// Samples drawn by each pen of a thumbnail.
+const THUMBNAIL_POINTS: f64 = 1500.0;
+/// Turns drawn by the thumbnails of curves that never close.
+const OPEN_CURVE_TURNS: f64 = 10.0;
+/// Points drawn as one line of the colour of its last point, egui being
+/// slow with a shape per segment.
+const COLOR_RUN: usize = 16;
+const MAX_STEPS: u32 = 10;
+
+/// Parameter changed from one thumbnail to the next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SweepParameter {
+    RingTeeth,
+    WheelTeeth,
+    /// Teeth of the wheel holding the pencils, the last of the chain.
+    LastWheelTeeth,
+    /// Hole of every pen, the pencil distance.
+    Hole,
+    /// Angle between consecutive pens.
+    PenSpread,
+}
+
+impl SweepParameter {
+    pub const ALL: [SweepParameter; 5] = [
+        SweepParameter::RingTeeth,
+        SweepParameter::WheelTeeth,
+        SweepParameter::LastWheelTeeth,
+        SweepParameter::Hole,
+        SweepParameter::PenSpread,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SweepParameter::RingTeeth => "Ring teeth",
+            SweepParameter::WheelTeeth => "Wheel teeth",
+            SweepParameter::LastWheelTeeth => "Last wheel teeth",
+            SweepParameter::Hole => "Pencil hole",
+            SweepParameter::PenSpread => "Pen spread (°)",
+        }
+    }
+
+    /// Values the parameter can take with the gears of the design, the
+    /// teeth keeping every wheel inside the gear it rolls in.
+    fn range(self, gears: &Gears, mode: SpirographeMode) -> RangeInclusive<f32> {
+        let teeth = |idx| {
+            let range = gears.teeth_range(mode, idx);
+            *range.start() as f32..=*range.end() as f32
+        };
+
+        match self {
+            SweepParameter::RingTeeth => teeth(0),
+            SweepParameter::WheelTeeth => teeth(1),
+            SweepParameter::LastWheelTeeth => teeth(gears.chain.len() + 1),
+            SweepParameter::Hole => 0.0..=(gears.hole_count() - 1) as f32,
+            SweepParameter::PenSpread => 0.0..=360.0,
+        }
+    }
+
+    fn apply(self, design: &mut Design, value: f32) {
+        let teeth = (value.round() as u32).max(MIN_TEETH);
+
+        match self {
+            SweepParameter::RingTeeth => design.gears.ring_teeth = teeth,
+            SweepParameter::WheelTeeth => design.gears.wheel_teeth = teeth,
+            SweepParameter::LastWheelTeeth => match design.gears.chain.last_mut() {
+                Some(gear) => gear.teeth = teeth,
+                None => design.gears.wheel_teeth = teeth,
+            },
+            SweepParameter::Hole => {
+                for pen in &mut design.pens {
+                    pen.hole = value.round().max(0.0) as u32;
+                }
+            }
+            SweepParameter::PenSpread => {
+                for (idx, pen) in design.pens.iter_mut().enumerate() {
+                    pen.phase = (idx as f32 * value).rem_euclid(360.0);
+                }
+            }
+        }
+    }
+}
+
+/// Values a parameter takes across the rows or the columns.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sweep {
+    pub parameter: SweepParameter,
+    pub from: f32,
+    pub to: f32,
+    pub steps: u32,
+}
+
+impl Sweep {
+    fn values(&self) -> Vec<f32> {
+        let steps = self.steps.clamp(1, MAX_STEPS);
+        let last = (steps - 1).max(1) as f32;
+
+        (0..steps)
+            .map(|step| self.from + (self.to - self.from) * step as f32 / last)
+            .collect()
+    }
+}
+
+#[derive(Resource)]
+pub struct GalleryConfig {
+    pub open: bool,
+    pub columns: Sweep,
+    pub rows: Sweep,
+    /// Width and height of each thumbnail, in points.
+    pub thumbnail_size: f32,
+}
+
+impl Default for GalleryConfig {
+    fn default() -> Self {
+        Self {
+            open: false,
+            columns: Sweep {
+                parameter: SweepParameter::WheelTeeth,
+                from: 24.0,
+                to: 84.0,
+                steps: 6,
+            },
+            rows: Sweep {
+                parameter: SweepParameter::Hole,
+                from: 0.0,
+                to: 10.0,
+                steps: 4,
+            },
+            thumbnail_size: 120.0,
+        }
+    }
+}
+
+struct Thumbnail {
+    design: Design,
+    /// The wheels fit in their gears and the pencils in their holes, the
+    /// curve being left out otherwise.
+    fits: bool,
+    traces: Vec<Trace>,
+    bounds: Rect,
+}
+
+/// Curves of the grid, kept until the design or the sweeps change.
+#[derive(Resource, Default)]
+struct Thumbnails {
+    /// What the grid being drawn, or last drawn, was drawn from.
+    drawn: Option<(Design, Sweep, Sweep)>,
+    /// Rows of thumbnails.
+    grid: Vec<Vec<Thumbnail>>,
+    /// Grid being drawn in the background, replacing `grid` once done.
+    task: Option<Task<Vec<Vec<Thumbnail>>>>,
+}
+
+/// Draws the whole curve of the design, with fewer points than the main view.
+fn render_thumbnail(design: Design) -> Thumbnail {
+    // Cells sweeping one parameter past where the other lets it go would
+    // otherwise show the gears the design falls back to
+    let gears = &design.gears;
+    let fits =
+        gears.fit(design.mode) && design.pens.iter().all(|pen| pen.hole < gears.hole_count());
+    if !fits {
+        return Thumbnail {
+            design,
+            fits,
+            traces: Vec::new(),
+            bounds: Rect::EMPTY,
+        };
+    }
+
+    let mut spirographe = design.build();
+
+    let length = match spirographe.period() {
+        Some(period) => period.length(),
+        None => OPEN_CURVE_TURNS * TAU,
+    };
+    spirographe.resolution = length / THUMBNAIL_POINTS;
+    spirographe.regenerate();
+    spirographe.advance(length);
+
+    let mut bounds = Rect::EMPTY;
+    for point in spirographe.traces.iter().flat_map(|trace| &trace.points) {
+        bounds = bounds.union_point(Vec2::new(point[0], point[1]));
+    }
+
+    Thumbnail {
+        design,
+        fits,
+        traces: spirographe.traces,
+        bounds,
+    }
+}
+
+/// Parts of the design the thumbnails depend on, the speed and the size of
+/// the ring making no difference once a curve is drawn whole and fitted in
+/// its box.
+fn thumbnail_key(design: &Design) -> Design {
+    Design {
+        ring_radius: DEFAULT_RING_RADIUS,
+        speed: 0.0,
+        ..design.clone()
+    }
+}
+
+fn render_grid(base: &Design, columns: &Sweep, rows: &Sweep) -> Vec<Vec<Thumbnail>> {
+    rows.values()
+        .into_iter()
+        .map(|row_value| {
+            columns
+                .values()
+                .into_iter()
+                .map(|column_value| {
+                    let mut design = base.clone();
+                    rows.parameter.apply(&mut design, row_value);
+                    columns.parameter.apply(&mut design, column_value);
+                    render_thumbnail(design)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Draws the grid again in the background whenever the design or the
+/// sweeps change, one grid at a time so dragging a slider only redraws it
+/// for the value it's at once the last one is done.
+fn update_thumbnails(
+    spirographe: Res<Spirographe>,
+    config: Res<GalleryConfig>,
+    mut thumbnails: ResMut<Thumbnails>,
+) {
+    if let Some(task) = &mut thumbnails.task {
+        let Some(grid) = block_on(future::poll_once(task)) else {
+            return;
+        };
+        thumbnails.grid = grid;
+        thumbnails.task = None;
+    }
+
+    let base = Design::capture(&spirographe);
+    let current = (thumbnail_key(&base), config.columns, config.rows);
+    if thumbnails.drawn.as_ref() == Some(&current) {
+        return;
+    }
+
+    let (columns, rows) = (config.columns, config.rows);
+    thumbnails.task =
+        Some(AsyncComputeTaskPool::get().spawn(async move { render_grid(&base, &columns, &rows) }));
+    thumbnails.drawn = Some(current);
+}
+
+fn sweep_ui(ui: &mut egui::Ui, label: &str, sweep: &mut Sweep, spirographe: &Spirographe) {
+    ui.horizontal(|ui| {
+        ui.label(label);
+
+        egui::ComboBox::from_id_source(label)
+            .selected_text(sweep.parameter.name())
+            .show_ui(ui, |ui| {
+                for parameter in SweepParameter::ALL {
+                    ui.selectable_value(&mut sweep.parameter, parameter, parameter.name());
+                }
+            });
+
+        let range = sweep.parameter.range(&spirographe.gears, spirographe.mode);
+        sweep.from = sweep.from.clamp(*range.start(), *range.end());
+        sweep.to = sweep.to.clamp(*range.start(), *range.end());
+
+        ui.add(egui::DragValue::new(&mut sweep.from).range(range.clone()));
+        ui.label("to");
+        ui.add(egui::DragValue::new(&mut sweep.to).range(range));
+        ui.add(
+            egui::DragValue::new(&mut sweep.steps)
+                .range(1..=MAX_STEPS)
+                .suffix(" steps"),
+        );
+    });
+}
+
+fn show_gallery(
+    mut ctx: EguiContexts,
+    mut config: ResMut<GalleryConfig>,
+    spirographe: Res<Spirographe>,
+    thumbnails: Res<Thumbnails>,
+    mut apply_design_event: EventWriter<ApplyDesignEvent>,
+) {
+    let mut open = config.open;
+    let mut picked = None;
+
+    egui::Window::new("Gallery")
+        .open(&mut open)
+        .show(ctx.ctx_mut(), |ui| {
+            sweep_ui(ui, "Columns", &mut config.columns, &spirographe);
+            sweep_ui(ui, "Rows", &mut config.rows, &spirographe);
+
+            ui.add(egui::Slider::new(&mut config.thumbnail_size, 40.0..=300.0).text("Size"));
+            if thumbnails.task.is_some() {
+                ui.label("Drawing...");
+            }
+
+            ui.separator();
+
+            egui::ScrollArea::both().show(ui, |ui| {
+                egui::Grid::new("Thumbnails").show(ui, |ui| {
+                    for row in &thumbnails.grid {
+                        for thumbnail in row {
+                            let response = show_thumbnail(ui, thumbnail, config.thumbnail_size);
+                            if response.clicked() && thumbnail.fits {
+                                picked = Some(thumbnail.design.clone());
+                            }
+                        }
+                        ui.end_row();
+                    }
+                });
+            });
+        });
+
+    if let Some(design) = picked {
+        apply_design_event.send(ApplyDesignEvent(design));
+        open = false;
+    }
+
+    if open != config.open {
+        config.open = open;
+    }
+}
+
+fn show_thumbnail(ui: &mut egui::Ui, thumbnail: &Thumbnail, size: f32) -> egui::Response {
+    let (rect, response) = ui.allocate_exact_size(egui::Vec2::splat(size), egui::Sense::click());
+    let painter = ui.painter_at(rect);
+
+    let background = if response.hovered() && thumbnail.fits {
+        egui::Color32::from_gray(45)
+    } else {
+        egui::Color32::from_gray(20)
+    };
+    painter.rect_filled(rect, 2.0, background);
+
+    // Fit the curve in the thumbnail, keeping its proportions and flipping y
+    let inner = rect.shrink(4.0);
+    let scale = inner.width() / thumbnail.bounds.size().max_element().max(1.0);
+    let center = thumbnail.bounds.center();
+    let to_screen = |point: &[f32; 3]| {
+        inner.center() + egui::vec2(point[0] - center.x, center.y - point[1]) * scale
+    };
+
+    for trace in &thumbnail.traces {
+        let mut start = 0;
+        while start + 1 < trace.points.len() {
+            let end = (start + COLOR_RUN).min(trace.points.len() - 1);
+            let [r, g, b, _] = trace.colors[end].map(|channel| (channel * 255.0) as u8);
+
+            painter.add(egui::Shape::line(
+                trace.points[start..=end].iter().map(to_screen).collect(),
+                egui::Stroke::new(1.0, egui::Color32::from_rgb(r, g, b)),
+            ));
+
+            start = end;
+        }
+    }
+
+    let gears = &thumbnail.design.gears;
+    let description = format!(
+        "{} / {} teeth, hole {}",
+        gears.ring_teeth,
+        gears.wheel_teeth,
+        thumbnail.design.pens.first().map_or(0, |pen| pen.hole)
+    );
+
+    if !thumbnail.fits {
+        painter.text(
+            rect.center(),
+            egui::Align2::CENTER_CENTER,
+            "Doesn't fit",
+            egui::FontId::proportional(12.0),
+            egui::Color32::from_gray(120),
+        );
+        return response.on_hover_text(format!("{description}: a wheel or a pencil doesn't fit"));
+    }
+
+    response.on_hover_text(description)
+}
@@ -0,0 +1,277 @@
+use std::{ops::RangeInclusive, sync::Arc};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    curve::{SpirographeMode, Wheel},
+    shapes::GearShape,
+};
+
+/// Fewest teeth a ring or a wheel can have.
+pub const MIN_TEETH: u32 = 8;
+/// Most teeth a ring or a wheel can have.
+pub const MAX_TEETH: u32 = 300;
+
+/// Distance between the edge of the wheel and its outermost pencil hole, as
+/// a fraction of the ring radius.
+const HOLE_MARGIN: f32 = 0.02;
+/// Distance between two consecutive pencil holes, as a fraction of the ring radius.
+const HOLE_SPACING: f32 = 0.03;
+
+/// Tooth counts of the rings and wheels that can be picked.
+#[derive(Resource)]
+pub struct GearCatalogue {
+    pub rings: Vec<u32>,
+    pub wheels: Vec<u32>,
+}
+
+impl Default for GearCatalogue {
+    /// The classic set, with its 96 and 105 teeth rings.
+    fn default() -> Self {
+        Self {
+            rings: vec![96, 105],
+            wheels: vec![
+                24, 30, 32, 40, 42, 45, 48, 52, 56, 60, 63, 64, 72, 75, 80, 84,
+            ],
+        }
+    }
+}
+
+/// Wheel rolling on the one before it in the chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainGear {
+    pub teeth: u32,
+    pub outside: bool,
+    pub reversed: bool,
+}
+
+/// Ring and wheels the curve is drawn with.
+///
+/// The ring is drawn with whatever radius is picked for it, so the teeth get
+/// smaller on rings with more of them and the wheels are scaled to match.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Gears {
+    pub ring_teeth: u32,
+    /// Wheel rolling on the ring or the bar.
+    pub wheel_teeth: u32,
+    /// Wheels rolling on the first one, each on the one before it.
+    pub chain: Vec<ChainGear>,
+    /// Shape of the ring, missing from designs saved before the shapes.
+    #[serde(default)]
+    pub ring_shape: GearShape,
+    /// Shape of the first wheel, the bar only rolling circles.
+    #[serde(default)]
+    pub wheel_shape: GearShape,
+}
+
+impl Default for Gears {
+    fn default() -> Self {
+        Self {
+            ring_teeth: 96,
+            wheel_teeth: 52,
+            chain: Vec::new(),
+            ring_shape: GearShape::Circle,
+            wheel_shape: GearShape::Circle,
+        }
+    }
+}
+
+impl Gears {
+    pub fn wheel_radius(&self, ring_radius: f32) -> f32 {
+        ring_radius * self.wheel_teeth as f32 / self.ring_teeth as f32
+    }
+
+    /// Radius of a wheel with `teeth`, sized to mesh with the ring.
+    pub fn radius(&self, teeth: u32, ring_radius: f32) -> f32 {
+        ring_radius * teeth as f32 / self.ring_teeth as f32
+    }
+
+    /// Every wheel, from the one rolling on the ring to the one holding the
+    /// pencil, none when drawing without gears.
+    ///
+    /// Only the ring and the first wheel take a shape, the wheels after it
+    /// are circles, the second one rolling on the first wheel's outline.
+    pub fn wheels(&self, mode: SpirographeMode, ring_radius: f32) -> Vec<Wheel> {
+        if !mode.has_gears() {
+            return Vec::new();
+        }
+
+        let (outline, track) = if mode == SpirographeMode::Bar {
+            (None, None)
+        } else {
+            (
+                self.wheel_shape.outline().map(Arc::new),
+                self.ring_shape.outline().map(Arc::new),
+            )
+        };
+
+        let first = Wheel {
+            radius: self.wheel_radius(ring_radius),
+            outside: mode == SpirographeMode::Epitrochoid,
+            reversed: false,
+            outline: outline.clone(),
+            track,
+        };
+
+        std::iter::once(first)
+            .chain(self.chain.iter().enumerate().map(|(idx, gear)| Wheel {
+                radius: self.radius(gear.teeth, ring_radius),
+                outside: gear.outside,
+                reversed: gear.reversed,
+                outline: None,
+                track: if idx == 0 { outline.clone() } else { None },
+            }))
+            .collect()
+    }
+
+    /// Tooth counts of the ring followed by every wheel.
+    pub fn teeth(&self) -> Vec<u32> {
+        [self.ring_teeth, self.wheel_teeth]
+            .into_iter()
+            .chain(self.chain.iter().map(|gear| gear.teeth))
+            .collect()
+    }
+
+    /// Teeth the gear `idx` of [`Gears::teeth`] can have, the ring being 0,
+    /// staying larger than the wheel rolling inside it and smaller than the
+    /// gear it rolls inside of.
+    pub fn teeth_range(&self, mode: SpirographeMode, idx: usize) -> RangeInclusive<u32> {
+        let teeth = self.teeth();
+        let rolls_inside = |idx: usize| match idx {
+            0 => false,
+            1 => mode == SpirographeMode::Hypotrochoid,
+            _ => !self.chain[idx - 2].outside,
+        };
+
+        let min = match teeth.get(idx + 1) {
+            Some(wheel) if rolls_inside(idx + 1) => wheel + 1,
+            _ => MIN_TEETH,
+        };
+        let max = if rolls_inside(idx) {
+            teeth[idx - 1] - 1
+        } else {
+            MAX_TEETH
+        };
+
+        min..=max.max(min)
+    }
+
+    /// Whether every wheel rolling inside a gear already fits in it.
+    pub fn fit(&self, mode: SpirographeMode) -> bool {
+        let mut clamped = self.clone();
+        clamped.clamp_teeth(mode) && clamped == *self
+    }
+
+    /// Teeth of the wheel holding the pencil.
+    pub fn pencil_wheel_teeth(&self) -> u32 {
+        self.chain
+            .last()
+            .map_or(self.wheel_teeth, |gear| gear.teeth)
+    }
+
+    /// Pencil holes in the last wheel, 0 being the closest to its edge.
+    pub fn hole_count(&self) -> u32 {
+        let radius = self.pencil_wheel_reach();
+        ((radius - HOLE_MARGIN).max(0.0) / HOLE_SPACING) as u32 + 1
+    }
+
+    pub fn hole_dist(&self, hole: u32, ring_radius: f32) -> f32 {
+        let radius = self.pencil_wheel_reach();
+        // Never past the center, where the pencil would swap sides
+        (radius - HOLE_MARGIN - hole as f32 * HOLE_SPACING).max(0.0) * ring_radius
+    }
+
+    /// Closest the edge of the last wheel comes to its center, for a ring of radius 1.
+    fn pencil_wheel_reach(&self) -> f32 {
+        let radius = self.radius(self.pencil_wheel_teeth(), 1.0);
+        if self.chain.is_empty() {
+            radius * self.wheel_shape.reach()
+        } else {
+            radius
+        }
+    }
+
+    /// Adds a wheel at the end of the chain, rolling inside the last one,
+    /// or around it when it's too small to hold another.
+    pub fn push_wheel(&mut self) {
+        let parent_teeth = self.pencil_wheel_teeth();
+        let outside = !holds_wheel(parent_teeth);
+        let teeth = if outside {
+            MIN_TEETH
+        } else {
+            (parent_teeth / 2).clamp(MIN_TEETH, parent_teeth - 1)
+        };
+
+        self.chain.push(ChainGear {
+            teeth,
+            outside,
+            reversed: false,
+        });
+    }
+
+    /// Shrinks the wheels rolling inside of a gear until they fit in it.
+    ///
+    /// Leaves the gears as they are and returns `false` when a gear is too
+    /// small to hold a wheel of [`MIN_TEETH`].
+    pub fn clamp_teeth(&mut self, mode: SpirographeMode) -> bool {
+        let mut clamped = self.clone();
+
+        if mode == SpirographeMode::Hypotrochoid {
+            if !holds_wheel(clamped.ring_teeth) {
+                return false;
+            }
+            clamped.wheel_teeth = clamped.wheel_teeth.clamp(MIN_TEETH, clamped.ring_teeth - 1);
+        }
+
+        let mut parent_teeth = clamped.wheel_teeth;
+        for gear in &mut clamped.chain {
+            if !gear.outside {
+                if !holds_wheel(parent_teeth) {
+                    return false;
+                }
+                gear.teeth = gear.teeth.clamp(MIN_TEETH, parent_teeth - 1);
+            }
+            parent_teeth = gear.teeth;
+        }
+
+        *self = clamped;
+        true
+    }
+
+    /// Picks the wheel `offset` places away in the catalogue.
+    pub fn cycle_wheel(&mut self, catalogue: &GearCatalogue, offset: isize) {
+        self.wheel_teeth = cycle(&catalogue.wheels, self.wheel_teeth, offset);
+    }
+
+    pub fn cycle_ring(&mut self, catalogue: &GearCatalogue, offset: isize) {
+        self.ring_teeth = cycle(&catalogue.rings, self.ring_teeth, offset);
+    }
+
+    /// Closest hole to `hole` the last wheel has, after it got smaller.
+    pub fn clamp_hole(&self, hole: u32) -> u32 {
+        hole.min(self.hole_count() - 1)
+    }
+
+    /// Hole `offset` holes towards the center of the wheel from `hole`.
+    pub fn move_hole(&self, hole: u32, offset: i32) -> u32 {
+        self.clamp_hole(hole.saturating_add_signed(offset))
+    }
+}
+
+/// Whether a wheel of [`MIN_TEETH`] fits inside a gear of `teeth`.
+pub fn holds_wheel(teeth: u32) -> bool {
+    teeth > MIN_TEETH
+}
+
+/// Entry `offset` places after `current` in `teeth`, wrapping around.
+///
+/// A count missing from the catalogue moves to its first entry.
+fn cycle(teeth: &[u32], current: u32, offset: isize) -> u32 {
+    let Some(idx) = teeth.iter().position(|count| *count == current) else {
+        return teeth.first().copied().unwrap_or(current);
+    };
+
+    let len = teeth.len() as isize;
+    teeth[(idx as isize + offset).rem_euclid(len) as usize]
+}
@@ -0,0 +1,77 @@
+use bevy::math::{DVec2, Vec2};
+use serde::{Deserialize, Serialize};
+
+/// Damped pendulum, swinging as `amplitude * e^(-decay * t) * sin(frequency * t + phase)`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Pendulum {
+    /// Swings per swing of a pendulum going once around per radian of `t`.
+    pub frequency: f32,
+    /// In degrees.
+    pub phase: f32,
+    /// Fraction of the ring radius it swings out to.
+    pub amplitude: f32,
+    /// How fast the swing dies out, per radian of `t`.
+    pub decay: f32,
+}
+
+impl Pendulum {
+    fn swing(&self, t: f64, phase: f64) -> (f64, f64) {
+        let angle = self.frequency as f64 * t + (self.phase as f64).to_radians() + phase;
+        let amplitude = self.amplitude as f64 * (-self.decay as f64 * t).exp();
+        (amplitude, angle)
+    }
+}
+
+/// Pen moved by two pendulums, one along each axis, over a paper that can
+/// be moved in circles by a third one, coupling both axes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Harmonograph {
+    pub x: Pendulum,
+    pub y: Pendulum,
+    pub rotary: Option<Pendulum>,
+}
+
+impl Default for Harmonograph {
+    fn default() -> Self {
+        Self {
+            x: Pendulum {
+                frequency: 2.0,
+                phase: 0.0,
+                amplitude: 0.45,
+                decay: 0.01,
+            },
+            y: Pendulum {
+                frequency: 3.01,
+                phase: 90.0,
+                amplitude: 0.45,
+                decay: 0.01,
+            },
+            rotary: None,
+        }
+    }
+}
+
+impl Harmonograph {
+    pub const DEFAULT_ROTARY: Pendulum = Pendulum {
+        frequency: 1.005,
+        phase: 0.0,
+        amplitude: 0.2,
+        decay: 0.005,
+    };
+
+    /// Where the pen is on the paper at `t`, `phase` radians being added to
+    /// the swing along x so several pens draw shifted figures.
+    pub fn position(&self, ring_radius: f32, phase: f32, t: f64) -> Vec2 {
+        let (x_amplitude, x_angle) = self.x.swing(t, phase as f64);
+        let (y_amplitude, y_angle) = self.y.swing(t, 0.0);
+
+        let mut position = DVec2::new(x_amplitude * x_angle.sin(), y_amplitude * y_angle.sin());
+
+        if let Some(rotary) = &self.rotary {
+            let (amplitude, angle) = rotary.swing(t, 0.0);
+            position += DVec2::new(angle.sin(), angle.cos()) * amplitude;
+        }
+
+        (position * ring_radius as f64).as_vec2()
+    }
+}
@@ -0,0 +1,345 @@
+use std::{collections::HashSet, mem};
+
+use bevy::prelude::*;
+use bevy_egui::EguiContexts;
+
+use crate::{presets::Design, trail::Trail, Spirographe, SpirographeMesh, Trace};
+
+pub struct LayersPlugin;
+
+impl Plugin for LayersPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (handle_layer_input, update_layer_trails).chain());
+    }
+}
+
+/// Distance along z between the trails of two consecutive layers.
+const LAYER_DEPTH: f32 = 0.001;
+
+/// Stroke kept once the parameters changed, drawn over the ones before it.
+pub struct Layer {
+    pub id: u64,
+    /// Curve drawn by each pen that was on the wheel.
+    traces: Vec<Trace>,
+    /// Where the wheel was when the stroke started and ended.
+    t_start: f64,
+    t_end: f64,
+    /// Parameters the stroke was drawn with, put back when undoing to it.
+    design: Design,
+    pub visible: bool,
+    /// Colour the whole layer is drawn with instead of its own.
+    tint: Option<[f32; 3]>,
+    /// Colours of the points before the layer was tinted.
+    untinted: Option<Vec<Vec<[f32; 4]>>>,
+}
+
+impl Layer {
+    pub fn tint(&self) -> Option<[f32; 3]> {
+        self.tint
+    }
+
+    pub fn set_tint(&mut self, tint: Option<[f32; 3]>) {
+        let untinted = self.untinted.get_or_insert_with(|| {
+            self.traces
+                .iter()
+                .map(|trace| trace.colors.clone())
+                .collect()
+        });
+
+        for (trace, colors) in self.traces.iter_mut().zip(untinted.iter()) {
+            trace.colors = match tint {
+                Some([r, g, b]) => vec![[r, g, b, 1.0]; colors.len()],
+                None => colors.clone(),
+            };
+            trace.mark_dirty(0);
+        }
+
+        self.tint = tint;
+    }
+}
+
+impl Spirographe {
+    /// Turns the current stroke into a layer if it drew anything.
+    pub(crate) fn keep_stroke(&mut self) {
+        if self.t <= self.stroke_start {
+            return;
+        }
+
+        let traces = self.traces.iter().map(Trace::continuation).collect();
+        let layer = self.take_stroke(traces);
+        self.layers.push(layer);
+        self.redo.clear();
+
+        self.stroke_start = self.t;
+    }
+
+    /// Replaces the traces of the current stroke, returning them as a layer.
+    fn take_stroke(&mut self, traces: Vec<Trace>) -> Layer {
+        let layer = Layer {
+            id: self.stroke_id,
+            traces: mem::replace(&mut self.traces, traces),
+            t_start: self.stroke_start,
+            t_end: self.t,
+            design: self
+                .stroke_design
+                .clone()
+                .unwrap_or_else(|| Design::capture(self)),
+            visible: true,
+            tint: None,
+            untinted: None,
+        };
+
+        self.stroke_id = self.next_layer_id;
+        self.next_layer_id += 1;
+
+        layer
+    }
+
+    /// Removes the last stroke drawn, moving the wheel back to where it
+    /// started and putting back the parameters it was drawn with.
+    pub fn undo(&mut self) {
+        let layer = if self.t > self.stroke_start {
+            let traces = self.traces.iter().map(Trace::restart).collect();
+            self.take_stroke(traces)
+        } else {
+            let Some(idx) = (0..self.layers.len()).max_by_key(|idx| self.layers[*idx].id) else {
+                return;
+            };
+            self.layers.remove(idx)
+        };
+
+        let traces = layer.traces.iter().map(Trace::restart).collect();
+        self.resume(&layer, layer.t_start, traces);
+        self.redo.push(layer);
+    }
+
+    /// Puts back the last stroke undone, moving the wheel to where it ended
+    /// and carrying on with its parameters.
+    pub fn redo(&mut self) {
+        let Some(layer) = self.redo.pop() else {
+            return;
+        };
+
+        let traces = layer.traces.iter().map(Trace::continuation).collect();
+        self.resume(&layer, layer.t_end, traces);
+        self.layers.push(layer);
+    }
+
+    /// Starts a new stroke at `t` with the parameters `layer` was drawn
+    /// with, the speed being left as it is.
+    fn resume(&mut self, layer: &Layer, t: f64, traces: Vec<Trace>) {
+        self.t = t;
+        self.stroke_start = t;
+        self.traces = traces;
+
+        let design = &layer.design;
+        self.mode = design.mode;
+        self.ring_radius = design.ring_radius;
+        self.gears = design.gears.clone();
+        self.pens = design.pens.clone();
+        self.harmonograph = design.harmonograph.clone();
+        self.parametric = design.parametric.clone();
+        self.hue_speed = design.hue_speed;
+
+        // Nothing was drawn since `stroke_start`, so no layer is left behind
+        self.update_radii();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.t > self.stroke_start || !self.layers.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    pub fn layers_mut(&mut self) -> &mut [Layer] {
+        &mut self.layers
+    }
+
+    /// Moves a layer `offset` places up, over the ones drawn after it.
+    pub fn move_layer(&mut self, idx: usize, offset: isize) {
+        let target = idx.saturating_add_signed(offset);
+        if target < self.layers.len() {
+            self.layers.swap(idx, target);
+        }
+    }
+
+    /// Traces of every visible layer, from the bottom up, then of the current stroke.
+    pub fn drawn_traces(&self) -> impl Iterator<Item = &Trace> {
+        self.layers
+            .iter()
+            .filter(|layer| layer.visible)
+            .flat_map(|layer| &layer.traces)
+            .chain(&self.traces)
+    }
+
+    pub(crate) fn trace_mut(&mut self, layer: u64, pen: usize) -> Option<&mut Trace> {
+        if layer == self.stroke_id {
+            return self.traces.get_mut(pen);
+        }
+
+        self.layers
+            .iter_mut()
+            .find(|candidate| candidate.id == layer)?
+            .traces
+            .get_mut(pen)
+    }
+
+    /// Every trail to draw, with its depth and visibility.
+    fn trail_keys(&self) -> impl Iterator<Item = (SpirographeMesh, f32, bool)> + '_ {
+        let current = (self.stroke_id, self.traces.len(), true);
+
+        self.layers
+            .iter()
+            .map(|layer| (layer.id, layer.traces.len(), layer.visible))
+            .chain(std::iter::once(current))
+            .enumerate()
+            .flat_map(move |(depth, (layer, pen_count, visible))| {
+                // The current stroke is on top, at depth 0
+                let z = (depth as f32 - self.layers.len() as f32) * LAYER_DEPTH;
+                (0..pen_count).map(move |pen| (SpirographeMesh { layer, pen }, z, visible))
+            })
+    }
+}
+
+fn handle_layer_input(
+    mut ctx: EguiContexts,
+    input: Res<ButtonInput<KeyCode>>,
+    mut spirographe: ResMut<Spirographe>,
+) {
+    // Undoing inside a text field only edits the text
+    let ctrl = input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if !ctrl || ctx.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+
+    if input.just_pressed(KeyCode::KeyZ) {
+        spirographe.undo();
+    }
+
+    if input.just_pressed(KeyCode::KeyY) {
+        spirographe.redo();
+    }
+}
+
+/// Spawns a trail for every pen of every layer and stacks them in the
+/// layers' order, despawning those of the layers undone.
+fn update_layer_trails(
+    mut commands: Commands,
+    spirographe: Res<Spirographe>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut trails: Query<(Entity, &SpirographeMesh, &mut Transform, &mut Visibility)>,
+) {
+    if !spirographe.is_changed() {
+        return;
+    }
+
+    let keys: Vec<_> = spirographe.trail_keys().collect();
+    let mut missing: HashSet<SpirographeMesh> = keys.iter().map(|(key, ..)| *key).collect();
+
+    for (entity, key, mut transform, mut visibility) in &mut trails {
+        let Some((_, z, visible)) = keys.iter().find(|(candidate, ..)| candidate == key) else {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        };
+        missing.remove(key);
+
+        if transform.translation.z != *z {
+            transform.translation.z = *z;
+        }
+
+        let new_visibility = if *visible {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        if *visibility != new_visibility {
+            *visibility = new_visibility;
+        }
+    }
+
+    for (key, z, visible) in keys {
+        if !missing.contains(&key) {
+            continue;
+        }
+
+        commands
+            .spawn(SpatialBundle {
+                transform: Transform::from_xyz(0.0, 0.0, z),
+                visibility: if visible {
+                    Visibility::Inherited
+                } else {
+                    Visibility::Hidden
+                },
+                ..Default::default()
+            })
+            .insert((key, Trail::new(materials.add(Color::srgb(1.0, 1.0, 1.0)))));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{curve::SpirographeMode, gears::Gears, Pen};
+
+    fn point_counts(spirographe: &Spirographe) -> Vec<usize> {
+        spirographe
+            .drawn_traces()
+            .map(|trace| trace.points.len())
+            .collect()
+    }
+
+    #[test]
+    fn undo_and_redo_strokes() {
+        let mut spirographe = Spirographe::new(
+            SpirographeMode::Hypotrochoid,
+            Gears::default(),
+            vec![Pen::default()],
+        );
+        assert!(!spirographe.can_undo());
+
+        spirographe.advance(1.0);
+        let first_end = spirographe.t;
+        let first_hole = spirographe.pens[0].hole;
+
+        // Changing a parameter keeps the stroke as a layer
+        spirographe.pens[0].hole = first_hole + 1;
+        spirographe.update_radii();
+        spirographe.advance(0.5);
+        let second_end = spirographe.t;
+        let drawn = point_counts(&spirographe);
+        assert!(drawn.iter().all(|count| *count > 1));
+        assert_eq!(spirographe.layers().len(), 1);
+
+        spirographe.undo();
+        assert_eq!(spirographe.t, first_end);
+        assert_eq!(spirographe.pens[0].hole, first_hole + 1);
+        assert_eq!(spirographe.layers().len(), 1);
+
+        spirographe.undo();
+        assert_eq!(spirographe.t, 0.0);
+        assert_eq!(spirographe.pens[0].hole, first_hole);
+        assert!(spirographe.layers().is_empty());
+        assert!(!spirographe.can_undo());
+        assert!(spirographe.can_redo());
+
+        spirographe.redo();
+        spirographe.redo();
+        assert_eq!(spirographe.t, second_end);
+        assert_eq!(spirographe.pens[0].hole, first_hole + 1);
+        assert_eq!(spirographe.layers().len(), 2);
+        assert!(!spirographe.can_redo());
+        // The layers come back whole, the current stroke starting empty
+        assert_eq!(point_counts(&spirographe)[..2], drawn[..2]);
+
+        // Drawing again drops what was undone
+        spirographe.undo();
+        spirographe.advance(0.25);
+        assert!(!spirographe.can_redo());
+    }
+}
@@ -1,6 +1,7 @@
 use std::f32::consts::PI;
 
 use bevy::{
+    math::DVec2,
     prelude::*,
     render::{
         mesh::{Indices, PrimitiveTopology},
@@ -8,11 +9,62 @@ use bevy::{
     },
     sprite::Mesh2dHandle,
 };
+use bevy_egui::{EguiContexts, EguiPlugin};
+use serde::{Deserialize, Serialize};
+
+mod camera;
+mod colors;
+mod curve;
+mod export;
+mod fourier;
+mod gallery;
+mod gears;
+mod harmonograph;
+mod layers;
+mod panel;
+mod parametric;
+mod plotter;
+mod presets;
+mod shapes;
+mod svg_path;
+mod trail;
+
+use camera::CameraPlugin;
+use colors::{ColorSample, ColorScheme};
+use curve::{
+    bar_length, CurvePoint, PencilMount, Period, SpirographeMode, Wheel, WheelPlacement,
+    DEFAULT_RING_RADIUS,
+};
+use export::ExportPlugin;
+use fourier::FourierPlugin;
+use gallery::GalleryPlugin;
+use gears::{GearCatalogue, Gears};
+use harmonograph::Harmonograph;
+use layers::{Layer, LayersPlugin};
+use panel::PanelPlugin;
+use parametric::ParametricCurves;
+use plotter::PlotterPlugin;
+use presets::{Design, PresetsPlugin};
+use shapes::Outline;
+use trail::TrailPlugin;
 
 fn main() {
     App::new()
-        .add_plugins(DefaultPlugins)
+        .add_plugins((
+            DefaultPlugins,
+            EguiPlugin,
+            CameraPlugin,
+            PanelPlugin,
+            ExportPlugin,
+            FourierPlugin,
+            GalleryPlugin,
+            LayersPlugin,
+            PlotterPlugin,
+            PresetsPlugin,
+            TrailPlugin,
+        ))
         .add_event::<ResetEvent>()
+        .init_resource::<GearCatalogue>()
         .add_systems(Startup, setup)
         .add_systems(Update, exit_app)
         .add_systems(
@@ -21,91 +73,523 @@ fn main() {
                 reset.run_if(on_event::<ResetEvent>()),
                 handle_input,
                 handle_spinning,
-                update_spirographe_mesh,
                 toggle_circle_visibility,
+                update_gear_meshes,
+                update_pencils,
             ),
         )
-        .add_systems(Last, update_transforms)
+        .add_systems(Last, (update_transforms, scale_guides))
         .run();
 }
 
-fn exit_app(input: Res<ButtonInput<KeyCode>>, mut exit_event: EventWriter<AppExit>) {
+fn exit_app(
+    mut ctx: EguiContexts,
+    input: Res<ButtonInput<KeyCode>>,
+    mut exit_event: EventWriter<AppExit>,
+) {
+    // Keys typed in the panel's text fields are theirs
+    if ctx.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+
     if input.just_pressed(KeyCode::Escape) {
         exit_event.send_default();
     }
 }
 
-const MAIN_CIRCLE_RADIUS: f32 = 500.0;
 const SPINNING_CIRCLE_SPEED: f32 = 1.0;
+/// Degrees of hue per radian rolled around the main circle.
 const HUE_CHANGIN_SPEED: f32 = 10.0;
 
-#[derive(Resource)]
-struct Spirographe {
-    spinning_circle_radius: f32,
-    spinning_circle_angle: f32,
-    spinning_circle_pos: Vec2,
-    pencil_dist: f32,
-    pencil_angle: f32,
-    pencil_position: Vec2,
-    hue: f32,
+/// Largest distance a point can be from the line between its neighbours and
+/// still be dropped.
+const DECIMATION_TOLERANCE: f32 = 0.05;
+/// Largest colour difference allowed between the ends of a merged segment.
+const DECIMATION_COLOR_TOLERANCE: f32 = 2.0 / 255.0;
+/// Most points kept for a stroke, past which it is decimated again more coarsely.
+const MAX_TRACE_POINTS: usize = 1 << 19;
+
+const DEFAULT_RESOLUTION: f64 = 0.01;
+const MIN_RESOLUTION: f64 = 0.0005;
+const MAX_RESOLUTION: f64 = 0.5;
+
+/// Pencil in a hole of the last wheel, drawing its own trail.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Pen {
+    hole: u32,
+    /// Angle of the hole on the wheel, in degrees.
+    phase: f32,
+    colors: ColorScheme,
+}
 
+impl Default for Pen {
+    fn default() -> Self {
+        Self {
+            hole: 2,
+            phase: 0.0,
+            colors: ColorScheme::default(),
+        }
+    }
+}
+
+/// Curve drawn by one pen during one stroke.
+#[derive(Clone, Default)]
+struct Trace {
     points: Vec<[f32; 3]>,
     colors: Vec<[f32; 4]>,
-    indices: Vec<u32>,
 
-    needs_update: bool,
+    /// First point replaced since the trail mesh was last updated.
+    dirty_from: Option<usize>,
+
+    /// Length of the curve up to the last sample, and up to the start of the stroke.
+    arc_length: f64,
+    stroke_arc_length: f64,
+    /// Last two samples of the stroke, the latest first, for its length and curvature.
+    last_samples: [Option<Vec2>; 2],
+    /// Times the decimation tolerances were doubled to keep the stroke under
+    /// `MAX_TRACE_POINTS`.
+    coarsening: i32,
+}
+
+impl Trace {
+    /// Records that the points from `first_point` on were replaced.
+    fn mark_dirty(&mut self, first_point: usize) {
+        self.dirty_from = Some(
+            self.dirty_from
+                .map_or(first_point, |dirty_from| dirty_from.min(first_point)),
+        );
+    }
+
+    /// Empty trace for the stroke drawn after this one, its length carrying on.
+    fn continuation(&self) -> Self {
+        Self {
+            arc_length: self.arc_length,
+            stroke_arc_length: self.arc_length,
+            ..Default::default()
+        }
+    }
+
+    /// Empty trace for this stroke to be drawn again.
+    fn restart(&self) -> Self {
+        Self {
+            arc_length: self.stroke_arc_length,
+            stroke_arc_length: self.stroke_arc_length,
+            ..Default::default()
+        }
+    }
+
+    /// Drops the points of the stroke.
+    fn clear_stroke(&mut self) {
+        self.points.clear();
+        self.colors.clear();
+        self.mark_dirty(0);
+
+        self.arc_length = self.stroke_arc_length;
+        self.last_samples = [None; 2];
+        self.coarsening = 0;
+    }
+
+    /// Follows the pencil to `pencil_pos`, measuring what the colour schemes need.
+    fn sample(&mut self, t: f64, pencil_pos: Vec2) -> ColorSample {
+        let mut curvature = 0.0;
+
+        if let Some(last) = self.last_samples[0] {
+            let segment = pencil_pos - last;
+            self.arc_length += segment.length() as f64;
+
+            // Turning angle over the length it turned along
+            if let Some(before) = self.last_samples[1] {
+                let previous = last - before;
+                let length = (previous.length() + segment.length()) / 2.0;
+                if length > f32::EPSILON {
+                    curvature = previous.angle_between(segment).abs() / length;
+                }
+            }
+        }
+
+        self.last_samples = [Some(pencil_pos), self.last_samples[0]];
+
+        ColorSample {
+            t,
+            position: pencil_pos,
+            arc_length: self.arc_length,
+            curvature,
+        }
+    }
+
+    fn push_point(&mut self, pencil_pos: Vec2, color: [f32; 4]) {
+        // Extend the last segment instead when it runs straight on, the
+        // same colour, so long sessions don't pile up redundant points
+        if self.can_extend_last_segment(pencil_pos, color) {
+            let last = self.points.len() - 1;
+            self.points[last] = [pencil_pos.x, pencil_pos.y, 0.0];
+            self.colors[last] = color;
+            self.mark_dirty(last);
+            return;
+        }
+
+        self.points.push([pencil_pos.x, pencil_pos.y, 0.0]);
+        self.colors.push(color);
+
+        if self.points.len() > MAX_TRACE_POINTS {
+            self.coarsen();
+        }
+    }
+
+    /// Decimates the whole stroke again with doubled tolerances until it
+    /// holds at most half of `MAX_TRACE_POINTS`, so its memory stays bounded
+    /// however long it is drawn for.
+    fn coarsen(&mut self) {
+        while self.points.len() > MAX_TRACE_POINTS / 2 {
+            self.coarsening += 1;
+
+            let points = std::mem::take(&mut self.points);
+            let colors = std::mem::take(&mut self.colors);
+            for (point, color) in points.into_iter().zip(colors) {
+                let pencil_pos = Vec2::new(point[0], point[1]);
+
+                if self.can_extend_last_segment(pencil_pos, color) {
+                    let last = self.points.len() - 1;
+                    self.points[last] = point;
+                    self.colors[last] = color;
+                } else {
+                    self.points.push(point);
+                    self.colors.push(color);
+                }
+            }
+        }
+
+        self.mark_dirty(0);
+    }
+
+    fn can_extend_last_segment(&self, pencil_pos: Vec2, color: [f32; 4]) -> bool {
+        // The first point of a stroke is kept, it's where the stroke can be redrawn from
+        if self.points.len() < 2 {
+            return false;
+        }
+
+        let [start, end] = [self.points.len() - 2, self.points.len() - 1]
+            .map(|idx| Vec2::new(self.points[idx][0], self.points[idx][1]));
+
+        let segment = pencil_pos - start;
+        let along = ((end - start).dot(segment) / segment.length_squared().max(f32::EPSILON))
+            .clamp(0.0, 1.0);
+        let scale = (self.coarsening as f32).exp2();
+        let straight = end.distance(start + segment * along) <= DECIMATION_TOLERANCE * scale;
+
+        let same_color = self.colors[self.points.len() - 2]
+            .iter()
+            .zip(color)
+            .all(|(a, b)| (a - b).abs() <= DECIMATION_COLOR_TOLERANCE * scale);
+
+        straight && same_color
+    }
+}
+
+#[derive(Resource)]
+struct Spirographe {
+    mode: SpirographeMode,
+    gears: Gears,
+    ring_radius: f32,
+    /// Radians rolled around the main circle per second.
+    speed: f32,
+    hue_speed: f32,
+    /// Every wheel of the chain, the pencils being on the last one.
+    wheels: Vec<Wheel>,
+    wheel_placements: Vec<WheelPlacement>,
+    pens: Vec<Pen>,
+    pencil_mounts: Vec<PencilMount>,
+    pencil_positions: Vec<Vec2>,
+    /// Pendulums moving the pencils in the harmonograph mode.
+    harmonograph: Harmonograph,
+    /// Equations of the curves drawn without gears.
+    parametric: ParametricCurves,
+
+    /// How far the spinning circle rolled, in radians around the main circle.
+    t: f64,
+    /// Step in `t` between two points of the curve.
+    resolution: f64,
+    /// `t` at which the parameters last changed.
+    stroke_start: f64,
+    /// Index, counted from `stroke_start`, of the next sample to draw.
+    next_sample: u64,
+    /// The current stroke went all around its period and closed on itself.
+    closed: bool,
+
+    /// Curve drawn by each pen since `stroke_start`, in the same order.
+    traces: Vec<Trace>,
+    /// Parameters the current stroke is drawn with, given to its layer.
+    stroke_design: Option<Design>,
+    /// Layer the current stroke becomes once the parameters change.
+    stroke_id: u64,
+    next_layer_id: u64,
+    /// Strokes drawn before the current one, from the bottom up.
+    layers: Vec<Layer>,
+    /// Strokes undone, the last one undone on top.
+    redo: Vec<Layer>,
 }
 
 impl Spirographe {
-    fn new() -> Self {
+    fn new(mode: SpirographeMode, gears: Gears, pens: Vec<Pen>) -> Self {
         let mut spirographe = Self {
-            spinning_circle_radius: 50.0,
-            spinning_circle_angle: 0.0,
-            spinning_circle_pos: Vec2::default(),
-            pencil_dist: 25.0,
-            pencil_angle: 0.0,
-            pencil_position: Vec2::default(),
-            hue: 0.0,
-
-            points: Vec::new(),
-            colors: Vec::new(),
-            indices: Vec::new(),
-
-            needs_update: false,
+            mode,
+            gears,
+            ring_radius: DEFAULT_RING_RADIUS,
+            speed: SPINNING_CIRCLE_SPEED,
+            hue_speed: HUE_CHANGIN_SPEED,
+            wheels: Vec::new(),
+            wheel_placements: Vec::new(),
+            traces: vec![Trace::default(); pens.len()],
+            pens,
+            pencil_mounts: Vec::new(),
+            pencil_positions: Vec::new(),
+            harmonograph: Harmonograph::default(),
+            parametric: ParametricCurves::default(),
+
+            t: 0.0,
+            resolution: DEFAULT_RESOLUTION,
+            stroke_start: 0.0,
+            next_sample: 0,
+            closed: false,
+
+            stroke_design: None,
+            stroke_id: 0,
+            next_layer_id: 1,
+            layers: Vec::new(),
+            redo: Vec::new(),
         };
 
-        spirographe.update_positions();
+        spirographe.update_radii();
 
         spirographe
     }
 
-    fn update_positions(&mut self) {
-        let dist = MAIN_CIRCLE_RADIUS - self.spinning_circle_radius;
-        let spinning_circle_pos = Vec2 {
-            x: f32::cos(self.spinning_circle_angle) * dist,
-            y: f32::sin(self.spinning_circle_angle) * dist,
-        };
+    /// Rolls the spinning circle further by `dt`, drawing every sample passed on the way.
+    ///
+    /// Stops once the curve closes.
+    fn advance(&mut self, dt: f64) {
+        if self.closed {
+            return;
+        }
+
+        self.t += dt;
+        self.redo.clear();
 
-        let pencil_pos = Vec2 {
-            x: spinning_circle_pos.x + f32::cos(self.pencil_angle) * self.pencil_dist,
-            y: spinning_circle_pos.y + f32::sin(self.pencil_angle) * self.pencil_dist,
+        if let Some(end) = self.stroke_end() {
+            if self.t >= end {
+                self.t = end;
+                self.closed = true;
+            }
+        }
+
+        self.draw_samples();
+        self.update_positions();
+    }
+
+    /// Draws the rest of the current stroke at once, until it closes.
+    fn complete(&mut self) {
+        let Some(end) = self.stroke_end() else {
+            return;
         };
 
-        self.spinning_circle_pos = spinning_circle_pos;
-        self.pencil_position = pencil_pos;
+        self.advance(end - self.t);
+    }
 
-        self.points.push([pencil_pos.x, pencil_pos.y, 0.0]);
+    fn period(&self) -> Option<Period> {
+        if self.mode.has_gears() {
+            curve::period(self.mode, &self.gears.teeth())
+        } else {
+            self.parametric.period(self.mode)
+        }
+    }
+
+    /// `t` at which the current stroke closes.
+    fn stroke_end(&self) -> Option<f64> {
+        self.period()
+            .map(|period| self.stroke_start + period.length())
+    }
+
+    /// Continues the curve from the current position with the gears changed,
+    /// keeping the current ones when a wheel can't fit inside its gear.
+    fn set_gears(&mut self, mut gears: Gears) {
+        if !gears.clamp_teeth(self.mode) {
+            return;
+        }
+        self.gears = gears;
+        self.update_radii();
+    }
+
+    /// Continues the curve from the current position on another shape,
+    /// unless the wheel can't fit inside the ring.
+    fn set_mode(&mut self, mode: SpirographeMode) {
+        let mut gears = self.gears.clone();
+        if !gears.clamp_teeth(mode) {
+            return;
+        }
+        self.mode = mode;
+        self.gears = gears;
+        self.update_radii();
+    }
+
+    /// Continues the curve from the current position with the main circle resized.
+    fn set_ring_radius(&mut self, ring_radius: f32) {
+        self.ring_radius = ring_radius;
+        self.update_radii();
+    }
+
+    /// Continues the curve from the current position with the pendulums changed.
+    fn set_harmonograph(&mut self, harmonograph: Harmonograph) {
+        self.harmonograph = harmonograph;
+        self.start_stroke();
+    }
+
+    /// Continues the curve from the current position with the equations changed.
+    fn set_parametric(&mut self, parametric: ParametricCurves) {
+        self.parametric = parametric;
+        self.start_stroke();
+    }
+
+    /// Continues the curves from the current position with the pens changed,
+    /// or only redraws them if just their colours did.
+    fn set_pens(&mut self, pens: Vec<Pen>) {
+        let moved = pens
+            .iter()
+            .zip(&self.pens)
+            .any(|(pen, old)| pen.hole != old.hole || pen.phase != old.phase);
+
+        self.pens = pens;
+
+        if moved {
+            self.update_radii();
+        } else {
+            self.regenerate();
+        }
+    }
+
+    /// Puts another pen on the last wheel, drawing from the current position.
+    fn add_pen(&mut self, pen: Pen) {
+        self.keep_stroke();
+        self.pens.push(pen);
+        self.traces.push(Trace::default());
+        self.update_radii();
+    }
+
+    fn remove_pen(&mut self, idx: usize) {
+        self.keep_stroke();
+        self.pens.remove(idx);
+        self.traces.remove(idx);
+        self.update_radii();
+    }
+
+    fn update_radii(&mut self) {
+        self.wheels = self.gears.wheels(self.mode, self.ring_radius);
+
+        for pen in &mut self.pens {
+            pen.hole = self.gears.clamp_hole(pen.hole);
+        }
+        self.pencil_mounts = self
+            .pens
+            .iter()
+            .map(|pen| PencilMount {
+                dist: self.gears.hole_dist(pen.hole, self.ring_radius),
+                phase: pen.phase.to_radians(),
+            })
+            .collect();
+
+        self.start_stroke();
+    }
+
+    /// Continues the curve from the current position with new parameters,
+    /// in a new layer.
+    ///
+    /// A stroke that didn't move yet is replaced instead, so dragging a
+    /// slider doesn't leave a layer behind for every value it went through.
+    fn start_stroke(&mut self) {
+        self.keep_stroke();
+        self.closed = false;
+
+        self.regenerate();
+        self.update_positions();
+    }
+
+    /// Redraws the curve since the last parameter change, e.g. after the
+    /// resolution or the colours changed.
+    fn regenerate(&mut self) {
+        for trace in &mut self.traces {
+            trace.clear_stroke();
+        }
+        self.next_sample = 0;
+        self.stroke_design = Some(Design::capture(self));
+
+        self.draw_samples();
+    }
+
+    /// Pushes the points of the curve at fixed steps of `t`, so the curve
+    /// doesn't depend on how often this is called.
+    fn draw_samples(&mut self) {
+        loop {
+            let sample_t = self.stroke_start + self.next_sample as f64 * self.resolution;
+            if sample_t > self.t {
+                break;
+            }
+
+            self.push_points(sample_t);
+            self.next_sample += 1;
+        }
+
+        // The end of the period rarely falls on a sample, join it exactly
+        if self.closed {
+            self.push_points(self.t);
+        }
+    }
+
+    fn push_points(&mut self, t: f64) {
+        let pencils = curve::pencil_positions(
+            self.mode,
+            self.ring_radius,
+            &self.wheels,
+            &self.pencil_mounts,
+            &self.harmonograph,
+            &self.parametric,
+            t,
+        );
 
-        let next_color = Color::hsv(self.hue, 1.0, 1.0).to_srgba();
+        for ((pen, trace), pencil_pos) in self.pens.iter().zip(&mut self.traces).zip(pencils) {
+            let sample = trace.sample(t, pencil_pos);
+            let color = pen.colors.color(&sample, self.hue_speed, self.ring_radius);
+            trace.push_point(pencil_pos, color);
+        }
+    }
 
-        let next_color = [next_color.red, next_color.green, next_color.blue, 1.0];
+    fn evaluate(&self, t: f64) -> CurvePoint {
+        curve::evaluate(
+            self.mode,
+            self.ring_radius,
+            &self.wheels,
+            &self.pencil_mounts,
+            &self.harmonograph,
+            &self.parametric,
+            t,
+        )
+    }
 
-        self.colors.push(next_color);
+    fn pencils_at(&self, t: f64) -> impl Iterator<Item = Vec2> + '_ {
+        curve::pencil_positions(
+            self.mode,
+            self.ring_radius,
+            &self.wheels,
+            &self.pencil_mounts,
+            &self.harmonograph,
+            &self.parametric,
+            t,
+        )
+    }
 
-        let new_index = self.indices.len() as u32;
-        self.indices.push(new_index);
+    fn update_positions(&mut self) {
+        let curve_point = self.evaluate(self.t);
 
-        self.needs_update = true;
+        self.wheel_placements = curve_point.wheels;
+        self.pencil_positions = curve_point.pencils;
     }
 }
 
@@ -113,13 +597,22 @@ impl Spirographe {
 struct MainCircle;
 
 #[derive(Component)]
-struct SpinningCircle;
+struct MainBar;
 
+/// Wheel of the chain, by its index in it.
 #[derive(Component)]
-struct Pencil;
+struct SpinningCircle(usize);
 
+/// Pencil of the pen with this index.
 #[derive(Component)]
-struct SpirographeMesh;
+struct Pencil(usize);
+
+/// Trail of a pen in a layer, the current stroke having its own layer id.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Hash)]
+struct SpirographeMesh {
+    layer: u64,
+    pen: usize,
+}
 
 #[derive(Event, Default)]
 struct ResetEvent;
@@ -129,62 +622,50 @@ fn setup(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
-    // Camera
-    commands.spawn(Camera2dBundle::default());
-
     // Main circle
     commands
         .spawn(ColorMesh2dBundle {
             mesh: meshes.add(create_circle_mesh()).into(),
             material: materials.add(Color::srgb(1.0, 1.0, 1.0)),
-            transform: Transform::from_scale(Vec3::ONE * MAIN_CIRCLE_RADIUS),
+            transform: Transform::from_scale(Vec3::ONE * DEFAULT_RING_RADIUS),
             ..Default::default()
         })
         .insert(MainCircle);
 
-    // Spinning circle
+    // Main bar
     commands
         .spawn(ColorMesh2dBundle {
-            mesh: meshes.add(create_circle_mesh()).into(),
+            mesh: meshes.add(create_bar_mesh()).into(),
             material: materials.add(Color::srgb(1.0, 1.0, 1.0)),
+            transform: Transform::from_scale(Vec3::new(
+                bar_length(DEFAULT_RING_RADIUS) / 2.0,
+                1.0,
+                1.0,
+            )),
             ..Default::default()
         })
-        .insert(SpinningCircle);
+        .insert(MainBar);
 
-    // Pencil
-    commands
-        .spawn(ColorMesh2dBundle {
-            mesh: meshes.add(Circle { radius: 5.0 }).into(),
-            material: materials.add(Color::srgb(1.0, 1.0, 1.0)),
-            ..Default::default()
-        })
-        .insert(Pencil);
-
-    let spirographe = Spirographe::new();
-
-    // Spirographe mesh
-    let spirographe_mesh = create_spirographe_mesh();
-    commands
-        .spawn(ColorMesh2dBundle {
-            mesh: meshes.add(spirographe_mesh).into(),
-            material: materials.add(Color::srgb(1.0, 1.0, 1.0)),
-            ..Default::default()
-        })
-        .insert(SpirographeMesh);
+    let spirographe = Spirographe::new(
+        SpirographeMode::Hypotrochoid,
+        Gears::default(),
+        vec![Pen::default()],
+    );
 
     commands.insert_resource(spirographe);
 }
 
-fn reset(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    spirographe_mesh: Query<&Mesh2dHandle, With<SpirographeMesh>>,
-) {
-    let new_spirographe = Spirographe::new();
-
-    let handle = spirographe_mesh.single();
-    let mesh = meshes.get_mut(&handle.0).unwrap();
-    *mesh = create_spirographe_mesh();
+fn reset(mut commands: Commands, spirographe: Res<Spirographe>) {
+    let mut new_spirographe = Spirographe::new(
+        spirographe.mode,
+        spirographe.gears.clone(),
+        spirographe.pens.clone(),
+    );
+    new_spirographe.speed = spirographe.speed;
+    new_spirographe.hue_speed = spirographe.hue_speed;
+    new_spirographe.set_ring_radius(spirographe.ring_radius);
+    new_spirographe.set_harmonograph(spirographe.harmonograph.clone());
+    new_spirographe.set_parametric(spirographe.parametric.clone());
 
     commands.insert_resource(new_spirographe);
 }
@@ -213,36 +694,118 @@ fn create_circle_mesh() -> Mesh {
     .with_inserted_indices(Indices::U32(indices))
 }
 
-fn create_spirographe_mesh() -> Mesh {
+/// Unit circle, or `outline` when the gear isn't round, with `teeth` teeth
+/// around it, pointing inwards for a ring the wheel rolls inside of.
+///
+/// Teeth are as deep as half their pitch, so they stay in proportion
+/// whatever the size the mesh is scaled to.
+fn create_gear_mesh(teeth: u32, inwards: bool, outline: Option<&Outline>) -> Mesh {
+    let pitch_angle = 2.0 * PI / teeth as f32;
+    let depth = if inwards { -0.25 } else { 0.25 } * pitch_angle;
+
+    // Each tooth is a trapezoid: root, tip, tip, root
+    let v_pos: Vec<[f32; 3]> = (0..teeth * 4)
+        .map(|i| {
+            let along = i as f32 * pitch_angle / 4.0;
+            let offset = match i % 4 {
+                0 | 3 => -depth,
+                _ => depth,
+            };
+
+            // Teeth stand along the normal, pointing out of the counterclockwise outline
+            let (point, tangent) = shapes::outline_at(outline, along as f64);
+            let normal = DVec2::new(tangent.sin(), -tangent.cos());
+            let vertex = (point + normal * offset as f64).as_vec2();
+
+            [vertex.x, vertex.y, 0.0]
+        })
+        .collect();
+
+    let mut indices: Vec<u32> = (0..teeth * 4).collect();
+    indices.push(0);
+
+    Mesh::new(
+        PrimitiveTopology::LineStrip,
+        RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD,
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, v_pos)
+    .with_inserted_indices(Indices::U32(indices))
+}
+
+fn create_bar_mesh() -> Mesh {
     Mesh::new(
         PrimitiveTopology::LineStrip,
         RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD,
     )
+    .with_inserted_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        vec![[-1.0, 0.0, 0.0], [1.0, 0.0, 0.0]],
+    )
+    .with_inserted_indices(Indices::U32(vec![0, 1]))
 }
 
 fn handle_input(
+    mut ctx: EguiContexts,
     input: Res<ButtonInput<KeyCode>>,
+    catalogue: Res<GearCatalogue>,
     mut spirographe: ResMut<Spirographe>,
     mut reset_event: EventWriter<ResetEvent>,
 ) {
+    if ctx.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+
+    let mut gears = spirographe.gears.clone();
+
     if input.just_pressed(KeyCode::ArrowUp) {
-        spirographe.spinning_circle_radius += 10.0;
-        spirographe.update_positions();
+        gears.cycle_wheel(&catalogue, 1);
     }
 
     if input.just_pressed(KeyCode::ArrowDown) {
-        spirographe.spinning_circle_radius -= 10.0;
-        spirographe.update_positions();
+        gears.cycle_wheel(&catalogue, -1);
+    }
+
+    if input.just_pressed(KeyCode::KeyG) {
+        gears.cycle_ring(&catalogue, 1);
+    }
+
+    if gears != spirographe.gears {
+        spirographe.set_gears(gears);
+    }
+
+    // The arrows move the first pen, the others are set from the panel
+    let mut pens = spirographe.pens.clone();
+    if let Some(pen) = pens.first_mut() {
+        if input.just_pressed(KeyCode::ArrowLeft) {
+            pen.hole = spirographe.gears.move_hole(pen.hole, 1);
+        }
+
+        if input.just_pressed(KeyCode::ArrowRight) {
+            pen.hole = spirographe.gears.move_hole(pen.hole, -1);
+        }
+    }
+
+    if pens != spirographe.pens {
+        spirographe.set_pens(pens);
     }
 
-    if input.just_pressed(KeyCode::ArrowLeft) {
-        spirographe.pencil_dist -= 10.0;
-        spirographe.update_positions();
+    if input.just_pressed(KeyCode::KeyM) {
+        let mode = spirographe.mode.next();
+        spirographe.set_mode(mode);
     }
 
-    if input.just_pressed(KeyCode::ArrowRight) {
-        spirographe.pencil_dist += 10.0;
-        spirographe.update_positions();
+    if input.just_pressed(KeyCode::BracketLeft) {
+        spirographe.resolution = (spirographe.resolution / 2.0).max(MIN_RESOLUTION);
+        spirographe.regenerate();
+    }
+
+    if input.just_pressed(KeyCode::BracketRight) {
+        spirographe.resolution = (spirographe.resolution * 2.0).min(MAX_RESOLUTION);
+        spirographe.regenerate();
+    }
+
+    if input.just_pressed(KeyCode::KeyC) {
+        spirographe.complete();
     }
 
     if input.just_pressed(KeyCode::KeyR) {
@@ -251,87 +814,216 @@ fn handle_input(
 }
 
 fn handle_spinning(
+    mut ctx: EguiContexts,
     time: Res<Time>,
     input: Res<ButtonInput<KeyCode>>,
     mut spirographe: ResMut<Spirographe>,
 ) {
-    if !input.pressed(KeyCode::Space) {
+    if !input.pressed(KeyCode::Space) || ctx.ctx_mut().wants_keyboard_input() {
         return;
     }
 
-    let dt = time.delta().as_secs_f32();
-    spirographe.spinning_circle_angle -= SPINNING_CIRCLE_SPEED * dt;
+    let dt = time.delta().as_secs_f64();
+    let speed = spirographe.speed as f64;
+    spirographe.advance(speed * dt);
+}
+
+fn update_transforms(
+    spirographe: Res<Spirographe>,
+    mut spinning_circles: Query<(&SpinningCircle, &mut Transform), Without<Pencil>>,
+    mut pencils: Query<(&Pencil, &mut Transform)>,
+) {
+    for (SpinningCircle(idx), mut transform) in &mut spinning_circles {
+        // Wheels removed from the chain are only despawned at the end of the frame
+        let (Some(wheel), Some(placement)) = (
+            spirographe.wheels.get(*idx),
+            spirographe.wheel_placements.get(*idx),
+        ) else {
+            continue;
+        };
+
+        transform.scale = Vec3::ONE * wheel.radius;
+        transform.rotation = Quat::from_rotation_z(placement.angle);
 
-    if spirographe.spinning_circle_angle <= 0.0 {
-        spirographe.spinning_circle_angle = 2.0 * PI;
+        transform.translation = Vec3 {
+            x: placement.center.x,
+            y: placement.center.y,
+            z: 0.0,
+        };
     }
 
-    spirographe.pencil_angle +=
-        (MAIN_CIRCLE_RADIUS / spirographe.spinning_circle_radius) * SPINNING_CIRCLE_SPEED * dt;
+    for (Pencil(idx), mut transform) in &mut pencils {
+        let Some(position) = spirographe.pencil_positions.get(*idx) else {
+            continue;
+        };
 
-    if spirographe.pencil_angle <= 0.0 {
-        spirographe.pencil_angle = 2.0 * PI;
+        transform.translation = Vec3 {
+            x: position.x,
+            y: position.y,
+            z: 0.0,
+        };
     }
+}
+
+type MainGuideFilter = Or<(With<MainCircle>, With<MainBar>)>;
 
-    spirographe.hue += HUE_CHANGIN_SPEED * dt;
-    if spirographe.hue > 360.0 {
-        spirographe.hue = 0.0;
+fn scale_guides(
+    spirographe: Res<Spirographe>,
+    mut guides: Query<(&mut Transform, Has<MainBar>), MainGuideFilter>,
+) {
+    if !spirographe.is_changed() {
+        return;
     }
 
-    spirographe.update_positions();
+    for (mut transform, is_main_bar) in &mut guides {
+        transform.scale = if is_main_bar {
+            Vec3::new(bar_length(spirographe.ring_radius) / 2.0, 1.0, 1.0)
+        } else {
+            Vec3::ONE * spirographe.ring_radius
+        };
+    }
 }
 
-fn update_spirographe_mesh(
+type GuideFilter = Or<(With<MainCircle>, With<MainBar>, With<SpinningCircle>)>;
+
+fn toggle_circle_visibility(
+    mut ctx: EguiContexts,
+    input: Res<ButtonInput<KeyCode>>,
     spirographe: Res<Spirographe>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    spirographe_mesh: Query<&Mesh2dHandle, With<SpirographeMesh>>,
+    mut circles_hidden: Local<bool>,
+    mut circles: Query<(&mut Visibility, Has<MainCircle>, Has<MainBar>), GuideFilter>,
 ) {
-    if !spirographe.needs_update {
-        return;
+    if input.just_pressed(KeyCode::KeyV) && !ctx.ctx_mut().wants_keyboard_input() {
+        *circles_hidden = !*circles_hidden;
     }
 
-    let handle = spirographe_mesh.single();
+    let on_bar = spirographe.mode == SpirographeMode::Bar;
+    let has_gears = spirographe.mode.has_gears();
 
-    let spirographe_mesh = meshes.get_mut(&handle.0).unwrap();
+    for (mut circle_vis, is_main_circle, is_main_bar) in &mut circles {
+        let visible = if is_main_circle {
+            !on_bar && has_gears
+        } else if is_main_bar {
+            on_bar
+        } else {
+            has_gears
+        };
 
-    spirographe_mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, spirographe.points.clone());
-    spirographe_mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, spirographe.colors.clone());
-    spirographe_mesh.insert_indices(Indices::U32(spirographe.indices.clone()));
+        let new_vis = if visible && !*circles_hidden {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+
+        if *circle_vis != new_vis {
+            *circle_vis = new_vis;
+        }
+    }
 }
 
-fn update_transforms(
+/// Redraws the teeth of the ring and respawns the wheels when they change.
+fn update_gear_meshes(
+    mut commands: Commands,
     spirographe: Res<Spirographe>,
-    mut spinning_circle: Query<&mut Transform, (With<SpinningCircle>, Without<Pencil>)>,
-    mut pencil: Query<&mut Transform, With<Pencil>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    main_circle: Query<&Mesh2dHandle, With<MainCircle>>,
+    spinning_circles: Query<Entity, With<SpinningCircle>>,
+    mut drawn: Local<Option<(Gears, SpirographeMode)>>,
 ) {
-    let mut transform = spinning_circle.single_mut();
-    transform.scale = Vec3::ONE * spirographe.spinning_circle_radius;
+    if drawn
+        .as_ref()
+        .is_some_and(|(gears, mode)| *gears == spirographe.gears && *mode == spirographe.mode)
+    {
+        return;
+    }
+    *drawn = Some((spirographe.gears.clone(), spirographe.mode));
 
-    transform.translation = Vec3 {
-        x: spirographe.spinning_circle_pos.x,
-        y: spirographe.spinning_circle_pos.y,
-        z: 0.0,
-    };
+    let teeth = spirographe.gears.teeth();
+    let wheels = &spirographe.wheels;
 
-    let mut pencil_transform = pencil.single_mut();
-    pencil_transform.translation = Vec3 {
-        x: spirographe.pencil_position.x,
-        y: spirographe.pencil_position.y,
-        z: 0.0,
+    // Teeth point inwards on gears with the next wheel rolling inside of them
+    let rolls_inside = |idx: usize| match idx {
+        0 => spirographe.mode == SpirographeMode::Hypotrochoid,
+        _ => wheels.get(idx).is_some_and(|wheel| !wheel.outside),
     };
+
+    let ring = meshes.get_mut(&main_circle.single().0).unwrap();
+    let ring_outline = wheels.first().and_then(|wheel| wheel.track.as_deref());
+    *ring = create_gear_mesh(teeth[0], rolls_inside(0), ring_outline);
+
+    for entity in &spinning_circles {
+        commands.entity(entity).despawn();
+    }
+
+    for (idx, wheel_teeth) in teeth[1..].iter().enumerate() {
+        commands
+            .spawn(ColorMesh2dBundle {
+                mesh: meshes
+                    .add(create_gear_mesh(
+                        *wheel_teeth,
+                        rolls_inside(idx + 1),
+                        wheels.get(idx).and_then(|wheel| wheel.outline.as_deref()),
+                    ))
+                    .into(),
+                material: materials.add(Color::srgb(1.0, 1.0, 1.0)),
+                ..Default::default()
+            })
+            .insert(SpinningCircle(idx));
+    }
 }
 
-fn toggle_circle_visibility(
-    input: Res<ButtonInput<KeyCode>>,
-    mut circles: Query<&mut Visibility, Or<(With<MainCircle>, With<SpinningCircle>)>>,
+/// Spawns a pencil for every pen, again whenever pens are added or removed.
+fn update_pencils(
+    mut commands: Commands,
+    spirographe: Res<Spirographe>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    pencils: Query<Entity, With<Pencil>>,
+    mut drawn: Local<Option<usize>>,
 ) {
-    if input.just_pressed(KeyCode::KeyV) {
-        for mut circle_vis in &mut circles {
-            if *circle_vis == Visibility::Inherited {
-                *circle_vis = Visibility::Hidden;
-            } else {
-                *circle_vis = Visibility::Inherited;
-            }
+    let pen_count = spirographe.pens.len();
+    if *drawn == Some(pen_count) {
+        return;
+    }
+    *drawn = Some(pen_count);
+
+    for entity in &pencils {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    for idx in 0..pen_count {
+        commands
+            .spawn(ColorMesh2dBundle {
+                mesh: meshes.add(Circle { radius: 5.0 }).into(),
+                material: materials.add(Color::srgb(1.0, 1.0, 1.0)),
+                ..Default::default()
+            })
+            .insert(Pencil(idx));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_strokes_stay_under_the_cap() {
+        let mut trace = Trace::default();
+
+        // A zigzag whose colour changes on every point, so nothing is merged at first
+        for i in 0..MAX_TRACE_POINTS * 3 {
+            let position = Vec2::new(i as f32 * 0.01, (i % 2) as f32);
+            let shade = (i % 256) as f32 / 255.0;
+            trace.push_point(position, [shade, 0.0, 0.0, 1.0]);
         }
+
+        assert!(trace.points.len() <= MAX_TRACE_POINTS);
+        assert_eq!(trace.points.len(), trace.colors.len());
+        assert_eq!(trace.points[0], [0.0; 3]);
+        assert_eq!(trace.dirty_from, Some(0));
+
+        let last = (MAX_TRACE_POINTS * 3 - 1) as f32;
+        assert_eq!(trace.points.last(), Some(&[last * 0.01, 1.0, 0.0]));
     }
 }
@@ -0,0 +1,751 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_egui::{
+    egui::{self, DragValue, Slider},
+    EguiContexts,
+};
+
+use crate::{
+    camera::{CameraConfig, FitDrawingEvent},
+    colors::{ColorMode, ColorScheme, ColorStop},
+    curve::SpirographeMode,
+    export::{ExportConfig, ExportPngEvent, ExportSvgEvent, MAX_PNG_SIZE},
+    fourier::{FourierConfig, LoadSvgPathEvent},
+    gallery::GalleryConfig,
+    gears::{holds_wheel, MAX_TEETH, MIN_TEETH},
+    harmonograph::{Harmonograph, Pendulum},
+    parametric::ParametricCurves,
+    plotter::{ExportPlotterEvent, PlotterConfig, PlotterFormat, PAPER_SIZES},
+    presets::{
+        builtin_presets, ApplyDesignEvent, LoadDesignEvent, PresetsConfig, RandomDesignEvent,
+        SaveDesignEvent,
+    },
+    shapes::{GearShape, MIN_ROUNDING},
+    trail::{LineJoin, TrailStyle},
+    Pen, ResetEvent, Spirographe, MAX_RESOLUTION, MIN_RESOLUTION,
+};
+
+pub struct PanelPlugin;
+
+impl Plugin for PanelPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PanelConfig>().add_systems(
+            Update,
+            (
+                ui,
+                draw_preview
+                    .after(ui)
+                    .run_if(|panel_config: Res<PanelConfig>| panel_config.show_preview),
+            ),
+        );
+    }
+}
+
+const MIN_RING_RADIUS: f32 = 50.0;
+const MAX_RING_RADIUS: f32 = 2000.0;
+
+/// Turns drawn by the preview of curves that never close.
+const OPEN_CURVE_PREVIEW_TURNS: u32 = 10;
+const MAX_PREVIEW_POINTS: f64 = 20_000.0;
+
+#[derive(Resource)]
+pub struct PanelConfig {
+    pub show_preview: bool,
+}
+
+impl Default for PanelConfig {
+    fn default() -> Self {
+        Self { show_preview: true }
+    }
+}
+
+#[derive(SystemParam)]
+struct DesignEvents<'w> {
+    save: EventWriter<'w, SaveDesignEvent>,
+    load: EventWriter<'w, LoadDesignEvent>,
+    random: EventWriter<'w, RandomDesignEvent>,
+    apply: EventWriter<'w, ApplyDesignEvent>,
+    load_svg_path: EventWriter<'w, LoadSvgPathEvent>,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn ui(
+    mut ctx: EguiContexts,
+    mut spirographe: ResMut<Spirographe>,
+    mut panel_config: ResMut<PanelConfig>,
+    mut reset_event: EventWriter<ResetEvent>,
+    mut export_config: ResMut<ExportConfig>,
+    mut export_svg_event: EventWriter<ExportSvgEvent>,
+    mut export_png_event: EventWriter<ExportPngEvent>,
+    mut plotter_config: ResMut<PlotterConfig>,
+    mut export_plotter_event: EventWriter<ExportPlotterEvent>,
+    mut trail_style: ResMut<TrailStyle>,
+    mut presets_config: ResMut<PresetsConfig>,
+    mut design_events: DesignEvents,
+    mut gallery_config: ResMut<GalleryConfig>,
+    mut camera_config: ResMut<CameraConfig>,
+    mut fit_drawing_event: EventWriter<FitDrawingEvent>,
+    mut fourier_config: ResMut<FourierConfig>,
+) {
+    egui::SidePanel::left("Side panel").show(ctx.ctx_mut(), |ui| {
+        ui.heading("Spirographe");
+
+        let mut mode = spirographe.mode;
+        egui::ComboBox::from_label("Curve")
+            .selected_text(mode.name())
+            .show_ui(ui, |ui| {
+                for candidate in SpirographeMode::ALL {
+                    ui.selectable_value(&mut mode, candidate, candidate.name());
+                }
+            });
+        if mode != spirographe.mode {
+            spirographe.set_mode(mode);
+        }
+
+        ui.separator();
+
+        let mut ring_radius = spirographe.ring_radius;
+        ui.add(
+            Slider::new(&mut ring_radius, MIN_RING_RADIUS..=MAX_RING_RADIUS).text("Ring radius"),
+        );
+        if ring_radius != spirographe.ring_radius {
+            spirographe.set_ring_radius(ring_radius);
+        }
+
+        if mode == SpirographeMode::Harmonograph {
+            let mut harmonograph = spirographe.harmonograph.clone();
+            harmonograph_ui(ui, &mut harmonograph);
+            if harmonograph != spirographe.harmonograph {
+                spirographe.set_harmonograph(harmonograph);
+            }
+        } else if !mode.has_gears() {
+            let mut parametric = spirographe.parametric.clone();
+            parametric_ui(ui, mode, &mut parametric);
+            if parametric != spirographe.parametric {
+                spirographe.set_parametric(parametric);
+            }
+
+            if mode == SpirographeMode::Epicycles {
+                ui.label("Draw a closed path holding the right mouse button");
+                ui.checkbox(&mut fourier_config.show_epicycles, "Show circles");
+
+                ui.text_edit_singleline(&mut fourier_config.svg_path);
+                if ui.button("Load SVG path").clicked() {
+                    design_events.load_svg_path.send_default();
+                }
+
+                if !fourier_config.status.is_empty() {
+                    ui.label(&fourier_config.status);
+                }
+            }
+        } else {
+            let mut gears = spirographe.gears.clone();
+
+            ui.horizontal(|ui| {
+                ui.label("Ring teeth");
+                // The ring has to hold the wheel rolling inside it
+                let min_ring_teeth = if mode == SpirographeMode::Hypotrochoid {
+                    MIN_TEETH + 1
+                } else {
+                    MIN_TEETH
+                };
+                ui.add(DragValue::new(&mut gears.ring_teeth).range(min_ring_teeth..=MAX_TEETH));
+            });
+
+            // A wheel rolling inside the ring has to be smaller than it
+            let max_wheel_teeth = if mode == SpirographeMode::Hypotrochoid {
+                gears.ring_teeth - 1
+            } else {
+                MAX_TEETH
+            };
+            gears.wheel_teeth = gears.wheel_teeth.min(max_wheel_teeth);
+
+            ui.add(
+                Slider::new(&mut gears.wheel_teeth, MIN_TEETH..=max_wheel_teeth)
+                    .text("Wheel teeth"),
+            );
+            ui.label(format!(
+                "Wheel radius: {:.1}",
+                gears.wheel_radius(spirographe.ring_radius)
+            ));
+
+            // The bar only rolls circles
+            if mode != SpirographeMode::Bar {
+                shape_ui(ui, "Ring shape", &mut gears.ring_shape);
+                shape_ui(ui, "Wheel shape", &mut gears.wheel_shape);
+            }
+
+            ui.collapsing("Chain", |ui| {
+                let mut parent_teeth = gears.wheel_teeth;
+                let mut removed = None;
+
+                for (idx, gear) in gears.chain.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        // A wheel rolling inside another has to be smaller than it
+                        let max_teeth = if gear.outside {
+                            MAX_TEETH
+                        } else {
+                            parent_teeth.saturating_sub(1).max(MIN_TEETH)
+                        };
+                        gear.teeth = gear.teeth.min(max_teeth);
+
+                        ui.label(format!("Wheel {}", idx + 2));
+                        ui.add(DragValue::new(&mut gear.teeth).range(MIN_TEETH..=max_teeth));
+                        ui.add_enabled_ui(holds_wheel(parent_teeth), |ui| {
+                            ui.selectable_value(&mut gear.outside, false, "Inside");
+                        });
+                        ui.selectable_value(&mut gear.outside, true, "Outside");
+                        ui.checkbox(&mut gear.reversed, "Reversed");
+
+                        if ui.button("Remove").clicked() {
+                            removed = Some(idx);
+                        }
+                    });
+
+                    parent_teeth = gear.teeth;
+                }
+
+                if let Some(idx) = removed {
+                    gears.chain.remove(idx);
+                }
+
+                if ui.button("Add wheel").clicked() {
+                    gears.push_wheel();
+                }
+            });
+
+            if gears != spirographe.gears {
+                spirographe.set_gears(gears);
+            }
+        }
+
+        ui.separator();
+
+        let mut pens = spirographe.pens.clone();
+        let mut removed = None;
+        let last_hole = spirographe.gears.hole_count() - 1;
+        let can_remove = pens.len() > 1;
+
+        for (idx, pen) in pens.iter_mut().enumerate() {
+            ui.collapsing(format!("Pen {}", idx + 1), |ui| {
+                // Without gears the pencils are only placed by their phase
+                if mode.has_gears() {
+                    ui.add(Slider::new(&mut pen.hole, 0..=last_hole).text("Hole"));
+                    ui.label(format!(
+                        "Distance: {:.1}",
+                        spirographe
+                            .gears
+                            .hole_dist(pen.hole, spirographe.ring_radius)
+                    ));
+                }
+                ui.add(Slider::new(&mut pen.phase, 0.0..=360.0).text("Phase (°)"));
+                color_scheme_ui(ui, &mut pen.colors);
+
+                if can_remove && ui.button("Remove pen").clicked() {
+                    removed = Some(idx);
+                }
+            });
+        }
+
+        if pens != spirographe.pens {
+            spirographe.set_pens(pens);
+        }
+
+        if let Some(idx) = removed {
+            spirographe.remove_pen(idx);
+        }
+
+        if ui.button("Add pen").clicked() {
+            // Spread the new pen around the wheel and the hue circle
+            let pen = Pen {
+                phase: (spirographe.pens.len() as f32 * 90.0) % 360.0,
+                colors: ColorScheme {
+                    hue_offset: (spirographe.pens.len() as f32 * 120.0) % 360.0,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            spirographe.add_pen(pen);
+        }
+
+        ui.separator();
+
+        let mut speed = spirographe.speed;
+        ui.add(
+            Slider::new(&mut speed, 0.05..=10.0)
+                .logarithmic(true)
+                .text("Speed"),
+        );
+        if speed != spirographe.speed {
+            spirographe.speed = speed;
+        }
+
+        let mut hue_speed = spirographe.hue_speed;
+        ui.add(Slider::new(&mut hue_speed, 0.0..=100.0).text("Hue speed"));
+        if hue_speed != spirographe.hue_speed {
+            spirographe.hue_speed = hue_speed;
+            spirographe.regenerate();
+        }
+
+        let mut resolution = spirographe.resolution;
+        ui.add(
+            Slider::new(&mut resolution, MIN_RESOLUTION..=MAX_RESOLUTION)
+                .logarithmic(true)
+                .text("Resolution"),
+        );
+        if resolution != spirographe.resolution {
+            spirographe.resolution = resolution;
+            spirographe.regenerate();
+        }
+
+        ui.separator();
+
+        // Only write back real edits, the whole trail is rebuilt when the style changes
+        let mut style = *trail_style;
+        ui.add(Slider::new(&mut style.width, 0.5..=20.0).text("Line width"));
+        ui.checkbox(&mut style.antialiasing, "Anti-aliasing");
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut style.join, LineJoin::Miter, "Miter joins");
+            ui.selectable_value(&mut style.join, LineJoin::Round, "Round joins");
+        });
+        if style != *trail_style {
+            *trail_style = style;
+        }
+
+        ui.separator();
+
+        match spirographe.period() {
+            Some(period) => {
+                ui.label(format!("Period: {} turns", period.turns));
+                ui.label(format!("Lobes: {}", period.lobes));
+            }
+            None => {
+                ui.label("Period: never closes");
+            }
+        }
+
+        if spirographe.closed {
+            ui.label("The curve is closed");
+        }
+
+        ui.checkbox(&mut panel_config.show_preview, "Preview");
+
+        ui.horizontal(|ui| {
+            if ui.button("Fit drawing").clicked() {
+                fit_drawing_event.send_default();
+            }
+
+            let mut follow_pencil = camera_config.follow_pencil;
+            ui.checkbox(&mut follow_pencil, "Follow pencil");
+            if follow_pencil != camera_config.follow_pencil {
+                camera_config.follow_pencil = follow_pencil;
+            }
+        });
+
+        ui.horizontal(|ui| {
+            if ui.button("Complete curve").clicked() {
+                spirographe.complete();
+            }
+
+            if ui.button("Reset").clicked() {
+                reset_event.send_default();
+            }
+        });
+
+        ui.separator();
+
+        ui.collapsing("Layers", |ui| {
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(spirographe.can_undo(), egui::Button::new("Undo"))
+                    .clicked()
+                {
+                    spirographe.undo();
+                }
+
+                if ui
+                    .add_enabled(spirographe.can_redo(), egui::Button::new("Redo"))
+                    .clicked()
+                {
+                    spirographe.redo();
+                }
+            });
+
+            // Listed from the top, the current stroke being over all of them
+            let layer_count = spirographe.layers().len();
+            let mut moved = None;
+
+            for idx in (0..layer_count).rev() {
+                let layer = &spirographe.layers()[idx];
+                let (id, was_visible, old_tint) = (layer.id, layer.visible, layer.tint());
+                let mut visible = was_visible;
+                let mut tinted = old_tint.is_some();
+                let mut tint = old_tint.unwrap_or([1.0, 1.0, 1.0]);
+
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut visible, format!("Layer {id}"));
+                    ui.checkbox(&mut tinted, "Tint");
+                    if tinted {
+                        ui.color_edit_button_rgb(&mut tint);
+                    }
+
+                    if ui
+                        .add_enabled(idx + 1 < layer_count, egui::Button::new("⬆"))
+                        .clicked()
+                    {
+                        moved = Some((idx, 1));
+                    }
+
+                    if ui.add_enabled(idx > 0, egui::Button::new("⬇")).clicked() {
+                        moved = Some((idx, -1));
+                    }
+                });
+
+                let new_tint = tinted.then_some(tint);
+                if visible != was_visible {
+                    spirographe.layers_mut()[idx].visible = visible;
+                }
+                if new_tint != old_tint {
+                    spirographe.layers_mut()[idx].set_tint(new_tint);
+                }
+            }
+
+            if let Some((idx, offset)) = moved {
+                spirographe.move_layer(idx, offset);
+            }
+        });
+
+        ui.collapsing("Designs", |ui| {
+            ui.horizontal_wrapped(|ui| {
+                for (name, design) in builtin_presets() {
+                    if ui.button(name).clicked() {
+                        design_events.apply.send(ApplyDesignEvent(design));
+                    }
+                }
+            });
+
+            ui.horizontal(|ui| {
+                if ui.button("Random design").clicked() {
+                    design_events.random.send_default();
+                }
+
+                if ui.button("Gallery").clicked() {
+                    gallery_config.open = true;
+                }
+            });
+
+            ui.text_edit_singleline(&mut presets_config.path);
+            ui.horizontal(|ui| {
+                if ui.button("Save").clicked() {
+                    design_events.save.send_default();
+                }
+
+                if ui.button("Load").clicked() {
+                    design_events.load.send_default();
+                }
+            });
+
+            if !presets_config.status.is_empty() {
+                ui.label(&presets_config.status);
+            }
+        });
+
+        ui.collapsing("Export", |ui| {
+            ui.add(Slider::new(&mut export_config.line_width, 0.5..=20.0).text("Line width"));
+            ui.checkbox(
+                &mut export_config.transparent_background,
+                "Transparent background",
+            );
+
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut export_config.svg_path);
+
+                if ui.button("Export SVG").clicked() {
+                    export_svg_event.send_default();
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("PNG size");
+                ui.add(DragValue::new(&mut export_config.png_size).range(16..=MAX_PNG_SIZE));
+            });
+
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut export_config.png_path);
+
+                if ui.button("Export PNG").clicked() {
+                    export_png_event.send_default();
+                }
+            });
+
+            if !export_config.status.is_empty() {
+                ui.label(&export_config.status);
+            }
+        });
+
+        ui.collapsing("Plotter", |ui| {
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut plotter_config.format, PlotterFormat::Gcode, "G-code");
+                ui.selectable_value(&mut plotter_config.format, PlotterFormat::Hpgl, "HPGL");
+            });
+
+            ui.horizontal(|ui| {
+                for (name, size) in PAPER_SIZES {
+                    if ui.button(name).clicked() {
+                        plotter_config.paper_size = size;
+                    }
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Paper (mm)");
+                ui.add(DragValue::new(&mut plotter_config.paper_size.x).range(10.0..=2000.0));
+                ui.add(DragValue::new(&mut plotter_config.paper_size.y).range(10.0..=2000.0));
+            });
+
+            let max_margin = plotter_config.paper_size.min_element() / 2.0;
+            ui.add(Slider::new(&mut plotter_config.margin, 0.0..=max_margin).text("Margin (mm)"));
+
+            ui.checkbox(&mut plotter_config.fit_to_paper, "Fit to paper");
+            if !plotter_config.fit_to_paper {
+                ui.add(
+                    Slider::new(&mut plotter_config.scale, 0.001..=10.0)
+                        .logarithmic(true)
+                        .text("mm per unit"),
+                );
+            }
+
+            ui.add(
+                Slider::new(&mut plotter_config.tolerance, 0.0..=2.0).text("Simplification (mm)"),
+            );
+            ui.add(Slider::new(&mut plotter_config.pen_count, 1..=12).text("Pens"));
+
+            if plotter_config.format == PlotterFormat::Gcode {
+                ui.horizontal(|ui| {
+                    ui.label("Pen up");
+                    ui.text_edit_singleline(&mut plotter_config.pen_up);
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Pen down");
+                    ui.text_edit_singleline(&mut plotter_config.pen_down);
+                });
+                ui.add(
+                    Slider::new(&mut plotter_config.feed_rate, 100.0..=10000.0)
+                        .text("Feed rate (mm/min)"),
+                );
+            }
+
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut plotter_config.path);
+
+                if ui.button("Export").clicked() {
+                    export_plotter_event.send_default();
+                }
+            });
+
+            if !plotter_config.status.is_empty() {
+                ui.label(&plotter_config.status);
+            }
+        });
+    });
+}
+
+fn harmonograph_ui(ui: &mut egui::Ui, harmonograph: &mut Harmonograph) {
+    pendulum_ui(ui, "Pendulum x", &mut harmonograph.x);
+    pendulum_ui(ui, "Pendulum y", &mut harmonograph.y);
+
+    let mut rotary = harmonograph.rotary.is_some();
+    ui.checkbox(&mut rotary, "Rotary paper");
+    if rotary != harmonograph.rotary.is_some() {
+        harmonograph.rotary = rotary.then_some(Harmonograph::DEFAULT_ROTARY);
+    }
+
+    if let Some(pendulum) = &mut harmonograph.rotary {
+        pendulum_ui(ui, "Paper", pendulum);
+    }
+}
+
+fn pendulum_ui(ui: &mut egui::Ui, label: &str, pendulum: &mut Pendulum) {
+    ui.collapsing(label, |ui| {
+        ui.add(Slider::new(&mut pendulum.frequency, 0.1..=10.0).text("Frequency"));
+        ui.add(Slider::new(&mut pendulum.phase, 0.0..=360.0).text("Phase (°)"));
+        ui.add(Slider::new(&mut pendulum.amplitude, 0.0..=1.0).text("Amplitude"));
+        ui.add(
+            Slider::new(&mut pendulum.decay, 0.0..=0.1)
+                .logarithmic(true)
+                .text("Decay"),
+        );
+    });
+}
+
+/// Picks the shape of a gear. Custom shapes come from designs, so they're
+/// only offered while the gear has one.
+fn shape_ui(ui: &mut egui::Ui, label: &str, shape: &mut GearShape) {
+    let mut choices = vec![
+        GearShape::Circle,
+        GearShape::Ellipse { aspect: 1.5 },
+        GearShape::Polygon {
+            sides: 5,
+            rounding: 0.3,
+        },
+    ];
+    if let GearShape::Convex { .. } = shape {
+        choices.push(shape.clone());
+    }
+
+    egui::ComboBox::from_label(label)
+        .selected_text(shape.name())
+        .show_ui(ui, |ui| {
+            for choice in choices {
+                let selected = choice.name() == shape.name();
+                if ui.selectable_label(selected, choice.name()).clicked() && !selected {
+                    *shape = choice;
+                }
+            }
+        });
+
+    match shape {
+        GearShape::Ellipse { aspect } => {
+            ui.add(
+                Slider::new(aspect, 0.2..=5.0)
+                    .logarithmic(true)
+                    .text("Aspect"),
+            );
+        }
+        GearShape::Polygon { sides, rounding } => {
+            ui.add(Slider::new(sides, 3..=12).text("Sides"));
+            ui.add(Slider::new(rounding, MIN_ROUNDING..=1.0).text("Rounding"));
+        }
+        GearShape::Circle | GearShape::Convex { .. } => {}
+    }
+}
+
+fn parametric_ui(ui: &mut egui::Ui, mode: SpirographeMode, parametric: &mut ParametricCurves) {
+    let integer = |ui: &mut egui::Ui, label: &str, value: &mut u32, max: u32| {
+        ui.horizontal(|ui| {
+            ui.label(label);
+            ui.add(DragValue::new(value).range(1..=max));
+        });
+    };
+
+    match mode {
+        SpirographeMode::Lissajous => {
+            let lissajous = &mut parametric.lissajous;
+            integer(ui, "Frequency x", &mut lissajous.a, 50);
+            integer(ui, "Frequency y", &mut lissajous.b, 50);
+            ui.add(Slider::new(&mut lissajous.phase, 0.0..=360.0).text("Phase (°)"));
+        }
+        SpirographeMode::Rose => {
+            let rose = &mut parametric.rose;
+            integer(ui, "Numerator", &mut rose.n, 50);
+            integer(ui, "Denominator", &mut rose.d, 50);
+        }
+        SpirographeMode::Superformula => {
+            let superformula = &mut parametric.superformula;
+            integer(ui, "Symmetry", &mut superformula.m, 50);
+            ui.add(Slider::new(&mut superformula.n1, 0.1..=20.0).text("n1"));
+            ui.add(Slider::new(&mut superformula.n2, 0.1..=20.0).text("n2"));
+            ui.add(Slider::new(&mut superformula.n3, 0.1..=20.0).text("n3"));
+            ui.add(Slider::new(&mut superformula.a, 0.1..=2.0).text("a"));
+            ui.add(Slider::new(&mut superformula.b, 0.1..=2.0).text("b"));
+        }
+        SpirographeMode::MaurerRose => {
+            let maurer = &mut parametric.maurer;
+            integer(ui, "Petals", &mut maurer.n, 50);
+            integer(ui, "Step (°)", &mut maurer.d, 359);
+        }
+        SpirographeMode::Epicycles => {
+            let epicycles = &mut parametric.epicycles;
+            let count = epicycles.circles.len().max(1);
+            ui.add(Slider::new(&mut epicycles.kept, 1..=count).text("Circles"));
+        }
+        _ => {}
+    }
+}
+
+fn color_scheme_ui(ui: &mut egui::Ui, colors: &mut ColorScheme) {
+    egui::ComboBox::from_id_source(ui.next_auto_id())
+        .selected_text(colors.mode.name())
+        .show_ui(ui, |ui| {
+            for mode in ColorMode::ALL {
+                ui.selectable_value(&mut colors.mode, mode, mode.name());
+            }
+        });
+
+    match colors.mode {
+        ColorMode::Rainbow => {
+            ui.add(Slider::new(&mut colors.hue_offset, 0.0..=360.0).text("Hue offset (°)"));
+            ui.add(Slider::new(&mut colors.saturation, 0.0..=1.0).text("Saturation"));
+            ui.add(Slider::new(&mut colors.value, 0.0..=1.0).text("Brightness"));
+        }
+        ColorMode::Solid => {
+            ui.horizontal(|ui| {
+                ui.label("Colour");
+                ui.color_edit_button_rgb(&mut colors.solid);
+            });
+        }
+        _ => {
+            ui.add(
+                Slider::new(&mut colors.scale, 0.05..=20.0)
+                    .logarithmic(true)
+                    .text("Scale"),
+            );
+
+            let mut removed = None;
+            let can_remove = colors.palette.len() > 1;
+            for (idx, stop) in colors.palette.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.add(
+                        DragValue::new(&mut stop.position)
+                            .range(0.0..=1.0)
+                            .speed(0.01),
+                    );
+                    ui.color_edit_button_rgb(&mut stop.color);
+
+                    if can_remove && ui.button("Remove").clicked() {
+                        removed = Some(idx);
+                    }
+                });
+            }
+
+            if let Some(idx) = removed {
+                colors.palette.remove(idx);
+            }
+
+            if ui.button("Add stop").clicked() {
+                colors.palette.push(ColorStop {
+                    position: 1.0,
+                    color: [1.0, 1.0, 1.0],
+                });
+            }
+
+            colors.sort_palette();
+        }
+    }
+}
+
+/// Outlines the whole curve the current parameters would draw.
+fn draw_preview(spirographe: Res<Spirographe>, mut gizmos: Gizmos) {
+    let length = match spirographe.period() {
+        Some(period) => period.length(),
+        None => OPEN_CURVE_PREVIEW_TURNS as f64 * std::f64::consts::TAU,
+    };
+
+    // The points budget is shared by all the pens
+    let pen_count = spirographe.pens.len().max(1) as f64;
+    let step = spirographe
+        .resolution
+        .max(length * pen_count / MAX_PREVIEW_POINTS);
+    let sample_count = (length / step).ceil() as u32;
+
+    let mut lines = vec![Vec::new(); spirographe.pens.len()];
+    for i in 0..=sample_count {
+        let t = spirographe.stroke_start + (i as f64 * step).min(length);
+        for (line, pencil_pos) in lines.iter_mut().zip(spirographe.pencils_at(t)) {
+            line.push(pencil_pos);
+        }
+    }
+
+    for line in lines {
+        gizmos.linestrip_2d(line, Color::srgba(1.0, 1.0, 1.0, 0.15));
+    }
+}
@@ -0,0 +1,211 @@
+use std::f64::consts::TAU;
+
+use bevy::math::{DVec2, Vec2};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    curve::{gcd, Period, SpirographeMode},
+    fourier::Epicycles,
+};
+
+/// Lines of a Maurer rose drawn per turn of `t`.
+const MAURER_STEPS_PER_TURN: u32 = 10;
+/// Lines of a Maurer rose, the points being a degree apart on the rose.
+const MAURER_STEPS: u32 = 360;
+
+/// `x = sin(a t + phase)`, `y = sin(b t)`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Lissajous {
+    pub a: u32,
+    pub b: u32,
+    /// In degrees.
+    pub phase: f32,
+}
+
+/// Rhodonea, `r = cos(n / d * t)`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Rose {
+    pub n: u32,
+    pub d: u32,
+}
+
+/// Gielis' superformula, `r = (|cos(m t / 4) / a|^n2 + |sin(m t / 4) / b|^n3)^(-1 / n1)`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Superformula {
+    pub m: u32,
+    pub n1: f32,
+    pub n2: f32,
+    pub n3: f32,
+    pub a: f32,
+    pub b: f32,
+}
+
+/// Lines joining the points of the rose `r = sin(n t)` taken every `d` degrees.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MaurerRose {
+    pub n: u32,
+    pub d: u32,
+}
+
+/// Parameters of every curve drawn from its equation instead of gears.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ParametricCurves {
+    pub lissajous: Lissajous,
+    pub rose: Rose,
+    pub superformula: Superformula,
+    pub maurer: MaurerRose,
+    /// Missing from designs saved before the epicycles.
+    #[serde(default)]
+    pub epicycles: Epicycles,
+}
+
+impl Default for Lissajous {
+    fn default() -> Self {
+        Self {
+            a: 3,
+            b: 4,
+            phase: 90.0,
+        }
+    }
+}
+
+impl Default for Rose {
+    fn default() -> Self {
+        Self { n: 5, d: 4 }
+    }
+}
+
+impl Default for Superformula {
+    fn default() -> Self {
+        Self {
+            m: 6,
+            n1: 1.0,
+            n2: 7.0,
+            n3: 8.0,
+            a: 1.0,
+            b: 1.0,
+        }
+    }
+}
+
+impl Default for MaurerRose {
+    fn default() -> Self {
+        Self { n: 6, d: 71 }
+    }
+}
+
+impl ParametricCurves {
+    /// Where the pencil is at `t` on the curve of `mode`, turned by `phase`
+    /// radians around the center so several pens draw turned copies.
+    pub fn position(&self, mode: SpirographeMode, ring_radius: f32, phase: f32, t: f64) -> Vec2 {
+        let point = match mode {
+            SpirographeMode::Lissajous => self.lissajous.position(t),
+            SpirographeMode::Rose => self.rose.position(t),
+            SpirographeMode::Superformula => self.superformula.position(t),
+            SpirographeMode::MaurerRose => self.maurer.position(t),
+            SpirographeMode::Epicycles => self.epicycles.position(t),
+            _ => DVec2::ZERO,
+        };
+
+        (DVec2::from_angle(phase as f64).rotate(point) * ring_radius as f64).as_vec2()
+    }
+
+    /// Length of `t` after which the curve of `mode` closes.
+    pub fn period(&self, mode: SpirographeMode) -> Option<Period> {
+        match mode {
+            SpirographeMode::Lissajous => self.lissajous.period(),
+            SpirographeMode::Rose => self.rose.period(),
+            // The lobes swap their sides every turn when there are an odd number of them
+            SpirographeMode::Superformula => Some(Period {
+                turns: if self.superformula.m % 2 == 1 { 2 } else { 1 },
+                lobes: self.superformula.m,
+            }),
+            SpirographeMode::MaurerRose => Some(Period {
+                turns: MAURER_STEPS / MAURER_STEPS_PER_TURN,
+                lobes: petals(self.maurer.n.max(1), 1),
+            }),
+            // Goes once around the path
+            SpirographeMode::Epicycles => Some(Period { turns: 1, lobes: 1 }),
+            _ => None,
+        }
+    }
+}
+
+impl Lissajous {
+    fn position(&self, t: f64) -> DVec2 {
+        DVec2::new(
+            (self.a as f64 * t + (self.phase as f64).to_radians()).sin(),
+            (self.b as f64 * t).sin(),
+        )
+    }
+
+    /// Integer frequencies always close after a turn, lobes counted along
+    /// the side the curve touches most.
+    fn period(&self) -> Option<Period> {
+        let divisor = gcd(self.a, self.b).max(1);
+        Some(Period {
+            turns: 1,
+            lobes: self.a.max(self.b) / divisor,
+        })
+    }
+}
+
+impl Rose {
+    fn position(&self, t: f64) -> DVec2 {
+        let k = self.n as f64 / self.d.max(1) as f64;
+        DVec2::from_angle(t) * (k * t).cos()
+    }
+
+    /// With `n / d` reduced, the rose closes after `d` turns, retracing itself
+    /// the second half when both are odd.
+    fn period(&self) -> Option<Period> {
+        let divisor = gcd(self.n, self.d);
+        if divisor == 0 {
+            return None;
+        }
+        let (n, d) = (self.n / divisor, self.d / divisor);
+
+        Some(Period {
+            turns: d,
+            lobes: petals(n, d),
+        })
+    }
+}
+
+impl Superformula {
+    fn position(&self, t: f64) -> DVec2 {
+        let angle = self.m as f64 * t / 4.0;
+        let cos = (angle.cos() / self.a as f64).abs().powf(self.n2 as f64);
+        let sin = (angle.sin() / self.b as f64).abs().powf(self.n3 as f64);
+        let r = (cos + sin).powf(-1.0 / self.n1 as f64);
+
+        // Flat parameters send the radius to infinity between the lobes
+        DVec2::from_angle(t) * if r.is_finite() { r } else { 0.0 }
+    }
+}
+
+impl MaurerRose {
+    /// Moves along the line between two points of the rose, a fraction of
+    /// a turn per line so every corner gets samples close to it.
+    fn position(&self, t: f64) -> DVec2 {
+        let step = t / TAU * MAURER_STEPS_PER_TURN as f64;
+        let start = step.floor();
+
+        self.vertex(start)
+            .lerp(self.vertex(start + 1.0), step - start)
+    }
+
+    fn vertex(&self, step: f64) -> DVec2 {
+        let angle = (step * self.d as f64).to_radians();
+        DVec2::from_angle(angle) * (self.n as f64 * angle).sin()
+    }
+}
+
+/// Petals of the rose `r = cos(n / d * t)`, `n / d` being reduced.
+fn petals(n: u32, d: u32) -> u32 {
+    if n % 2 == 1 && d % 2 == 1 {
+        n
+    } else {
+        2 * n
+    }
+}
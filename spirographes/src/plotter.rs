@@ -0,0 +1,349 @@
+use std::{fmt::Write, fs, io};
+
+use bevy::prelude::*;
+
+use crate::{Spirographe, Trace};
+
+pub struct PlotterPlugin;
+
+impl Plugin for PlotterPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ExportPlotterEvent>()
+            .init_resource::<PlotterConfig>()
+            .add_systems(
+                Update,
+                export_plotter.run_if(on_event::<ExportPlotterEvent>()),
+            );
+    }
+}
+
+/// HPGL coordinates are in plotter units of 0.025 mm.
+const HPGL_UNITS_PER_MM: f32 = 40.0;
+
+#[derive(Event, Default)]
+pub struct ExportPlotterEvent;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlotterFormat {
+    Gcode,
+    Hpgl,
+}
+
+#[derive(Resource)]
+pub struct PlotterConfig {
+    pub format: PlotterFormat,
+    pub path: String,
+    /// Paper width and height, in millimeters.
+    pub paper_size: Vec2,
+    /// Blank space kept around the drawing, in millimeters.
+    pub margin: f32,
+    /// Scales the drawing to fill the paper, otherwise `scale` is used.
+    pub fit_to_paper: bool,
+    /// Millimeters per world unit.
+    pub scale: f32,
+    /// Commands lifting and lowering the pen, for G-code. HPGL has its own `PU` and `PD`.
+    pub pen_up: String,
+    pub pen_down: String,
+    /// Drawing speed for G-code, in millimeters per minute.
+    pub feed_rate: f32,
+    /// Largest distance, in millimeters, a simplified path may stray from the curve.
+    pub tolerance: f32,
+    /// The hue circle is split into this many bands, each drawn with its own pen.
+    pub pen_count: u32,
+    pub status: String,
+}
+
+impl Default for PlotterConfig {
+    fn default() -> Self {
+        Self {
+            format: PlotterFormat::Gcode,
+            path: String::from("spirographe.gcode"),
+            paper_size: PAPER_SIZES[0].1,
+            margin: 10.0,
+            fit_to_paper: true,
+            scale: 0.1,
+            pen_up: String::from("G0 Z5"),
+            pen_down: String::from("G0 Z0"),
+            feed_rate: 1500.0,
+            tolerance: 0.05,
+            pen_count: 1,
+            status: String::new(),
+        }
+    }
+}
+
+/// Common paper sizes, portrait, in millimeters.
+pub const PAPER_SIZES: [(&str, Vec2); 4] = [
+    ("A4", Vec2::new(210.0, 297.0)),
+    ("A3", Vec2::new(297.0, 420.0)),
+    ("Letter", Vec2::new(215.9, 279.4)),
+    ("Tabloid", Vec2::new(279.4, 431.8)),
+];
+
+/// Path drawn without lifting the pen, in millimeters on the paper.
+type Path = Vec<Vec2>;
+
+/// Splits the curves into one set of paths per plotter pen.
+///
+/// Each segment is given to the pen of the hue band its end point falls in,
+/// like the colours of the trail.
+fn pen_layers(traces: &[Trace], pen_count: u32) -> Vec<Vec<Path>> {
+    let pen_count = pen_count.max(1);
+    let mut layers = vec![Vec::new(); pen_count as usize];
+
+    for trace in traces {
+        push_layer_paths(&mut layers, &trace.points, &trace.colors);
+    }
+
+    layers
+}
+
+fn push_layer_paths(layers: &mut [Vec<Path>], points: &[[f32; 3]], colors: &[[f32; 4]]) {
+    let pen_count = layers.len() as u32;
+
+    let mut current: Option<(usize, Path)> = None;
+    for (segment, color) in points.windows(2).zip(colors.iter().skip(1)) {
+        let hue = Hsva::from(Color::srgb(color[0], color[1], color[2])).hue;
+        let pen = ((hue / 360.0 * pen_count as f32) as usize).min(pen_count as usize - 1);
+
+        let from = Vec2::new(segment[0][0], segment[0][1]);
+        let to = Vec2::new(segment[1][0], segment[1][1]);
+
+        match &mut current {
+            Some((current_pen, path)) if *current_pen == pen => path.push(to),
+            _ => {
+                if let Some((current_pen, path)) = current.take() {
+                    layers[current_pen].push(path);
+                }
+                current = Some((pen, vec![from, to]));
+            }
+        }
+    }
+
+    if let Some((pen, path)) = current {
+        layers[pen].push(path);
+    }
+}
+
+/// Maps world coordinates to millimeters on the paper, centering the drawing.
+fn paper_transform(traces: &[Trace], config: &PlotterConfig) -> impl Fn(Vec2) -> Vec2 {
+    let mut bounds = Rect::EMPTY;
+    for point in traces.iter().flat_map(|trace| &trace.points) {
+        bounds = bounds.union_point(Vec2::new(point[0], point[1]));
+    }
+
+    let printable = (config.paper_size - 2.0 * config.margin).max(Vec2::ONE);
+    let scale = if config.fit_to_paper {
+        (printable / bounds.size().max(Vec2::ONE)).min_element()
+    } else {
+        config.scale
+    };
+
+    let center = bounds.center();
+    let paper_center = config.paper_size / 2.0;
+    move |point| paper_center + (point - center) * scale
+}
+
+/// Ramer-Douglas-Peucker simplification, keeps the points needed for the path
+/// to stay within `tolerance` of the original.
+fn simplify(path: &[Vec2], tolerance: f32) -> Path {
+    if path.len() < 3 {
+        return path.to_vec();
+    }
+
+    let mut keep = vec![false; path.len()];
+    keep[0] = true;
+    keep[path.len() - 1] = true;
+
+    let mut ranges = vec![(0, path.len() - 1)];
+    while let Some((start, end)) = ranges.pop() {
+        let (a, b) = (path[start], path[end]);
+
+        let farthest = (start + 1..end)
+            .map(|idx| (idx, line_distance(path[idx], a, b)))
+            .max_by(|(_, d1), (_, d2)| d1.total_cmp(d2));
+
+        if let Some((idx, dist)) = farthest {
+            if dist > tolerance {
+                keep[idx] = true;
+                ranges.push((start, idx));
+                ranges.push((idx, end));
+            }
+        }
+    }
+
+    path.iter()
+        .zip(keep)
+        .filter_map(|(point, keep)| keep.then_some(*point))
+        .collect()
+}
+
+fn line_distance(point: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let ab = b - a;
+    if ab.length_squared() <= f32::EPSILON {
+        return point.distance(a);
+    }
+
+    ab.perp_dot(point - a).abs() / ab.length()
+}
+
+fn write_gcode(layers: &[Vec<Path>], config: &PlotterConfig) -> String {
+    let mut gcode = String::new();
+
+    let _ = writeln!(gcode, "G21 ; millimeters");
+    let _ = writeln!(gcode, "G90 ; absolute positions");
+    let _ = writeln!(gcode, "{}", config.pen_up);
+
+    for (pen, paths) in layers.iter().enumerate() {
+        if paths.is_empty() {
+            continue;
+        }
+
+        if layers.len() > 1 {
+            let _ = writeln!(gcode, "M0 ; insert pen {}", pen + 1);
+        }
+
+        for path in paths {
+            let _ = writeln!(gcode, "G0 X{:.3} Y{:.3}", path[0].x, path[0].y);
+            let _ = writeln!(gcode, "{}", config.pen_down);
+
+            for point in &path[1..] {
+                let _ = writeln!(
+                    gcode,
+                    "G1 X{:.3} Y{:.3} F{}",
+                    point.x, point.y, config.feed_rate
+                );
+            }
+
+            let _ = writeln!(gcode, "{}", config.pen_up);
+        }
+    }
+
+    let _ = writeln!(gcode, "G0 X0 Y0");
+
+    gcode
+}
+
+fn write_hpgl(layers: &[Vec<Path>]) -> String {
+    let to_units = |point: Vec2| (point * HPGL_UNITS_PER_MM).round().as_ivec2();
+    let mut hpgl = String::from("IN;\n");
+
+    for (pen, paths) in layers.iter().enumerate() {
+        if paths.is_empty() {
+            continue;
+        }
+
+        let _ = writeln!(hpgl, "SP{};", pen + 1);
+
+        for path in paths {
+            let start = to_units(path[0]);
+            let _ = writeln!(hpgl, "PU{},{};", start.x, start.y);
+
+            let coords = path[1..]
+                .iter()
+                .map(|point| {
+                    let point = to_units(*point);
+                    format!("{},{}", point.x, point.y)
+                })
+                .collect::<Vec<_>>()
+                .join(",");
+            let _ = writeln!(hpgl, "PD{coords};");
+        }
+    }
+
+    hpgl.push_str("PU;SP0;\n");
+
+    hpgl
+}
+
+fn plot(spirographe: &Spirographe, config: &PlotterConfig) -> io::Result<()> {
+    let traces: Vec<Trace> = spirographe.drawn_traces().cloned().collect();
+    let to_paper = paper_transform(&traces, config);
+
+    let layers: Vec<Vec<Path>> = pen_layers(&traces, config.pen_count)
+        .into_iter()
+        .map(|paths| {
+            paths
+                .into_iter()
+                .map(|path| {
+                    let path: Path = path.into_iter().map(&to_paper).collect();
+                    simplify(&path, config.tolerance)
+                })
+                .collect()
+        })
+        .collect();
+
+    let output = match config.format {
+        PlotterFormat::Gcode => write_gcode(&layers, config),
+        PlotterFormat::Hpgl => write_hpgl(&layers),
+    };
+
+    fs::write(&config.path, output)
+}
+
+fn export_plotter(spirographe: Res<Spirographe>, mut config: ResMut<PlotterConfig>) {
+    config.status = match plot(&spirographe, &config) {
+        Ok(()) => format!("Exported {}", config.path),
+        Err(e) => format!("Export failed: {e}"),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::TAU;
+
+    use super::*;
+
+    #[test]
+    fn short_paths_are_kept() {
+        let path = vec![Vec2::ZERO, Vec2::X];
+        assert_eq!(simplify(&path, 1.0), path);
+    }
+
+    #[test]
+    fn straight_runs_are_merged() {
+        let path: Path = (0..10).map(|i| Vec2::new(i as f32, 0.0)).collect();
+        assert_eq!(simplify(&path, 0.01), vec![Vec2::ZERO, Vec2::new(9.0, 0.0)]);
+
+        // Corners stay, the wobble below the tolerance doesn't
+        let path = vec![
+            Vec2::ZERO,
+            Vec2::new(1.0, 0.05),
+            Vec2::new(2.0, 0.0),
+            Vec2::new(2.0, 2.0),
+        ];
+        assert_eq!(
+            simplify(&path, 0.1),
+            vec![Vec2::ZERO, Vec2::new(2.0, 0.0), Vec2::new(2.0, 2.0)]
+        );
+    }
+
+    #[test]
+    fn simplified_path_stays_within_tolerance() {
+        let tolerance = 0.05;
+        // Closed loop, its ends at the same place
+        let path: Path = (0..=500)
+            .map(|i| {
+                let angle = i as f32 / 500.0 * TAU;
+                Vec2::from_angle(angle) * (10.0 + (angle * 5.0).sin())
+            })
+            .collect();
+
+        let simplified = simplify(&path, tolerance);
+        assert!(simplified.len() > 2 && simplified.len() < path.len() / 2);
+        assert_eq!(simplified.first(), path.first());
+        assert_eq!(simplified.last(), path.last());
+
+        for point in &path {
+            let distance = simplified
+                .windows(2)
+                .map(|segment| {
+                    let ab = segment[1] - segment[0];
+                    let t = ((*point - segment[0]).dot(ab) / ab.length_squared()).clamp(0.0, 1.0);
+                    point.distance(segment[0] + ab * t)
+                })
+                .fold(f32::INFINITY, f32::min);
+            assert!(distance <= tolerance + 1e-4, "{point} is {distance} away");
+        }
+    }
+}
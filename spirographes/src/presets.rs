@@ -0,0 +1,469 @@
+use std::{fs, io, path::Path};
+
+use bevy::prelude::*;
+use rand::{seq::IndexedRandom, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    colors::{ColorMode, ColorScheme, ColorStop},
+    curve::{self, SpirographeMode, DEFAULT_RING_RADIUS},
+    gears::{ChainGear, GearCatalogue, Gears, MIN_TEETH},
+    harmonograph::{Harmonograph, Pendulum},
+    parametric::ParametricCurves,
+    shapes::GearShape,
+    Pen, Spirographe, HUE_CHANGIN_SPEED, SPINNING_CIRCLE_SPEED,
+};
+
+pub struct PresetsPlugin;
+
+impl Plugin for PresetsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SaveDesignEvent>()
+            .add_event::<LoadDesignEvent>()
+            .add_event::<ApplyDesignEvent>()
+            .add_event::<RandomDesignEvent>()
+            .init_resource::<PresetsConfig>()
+            .add_systems(
+                Update,
+                (
+                    save_design.run_if(on_event::<SaveDesignEvent>()),
+                    load_design.run_if(on_event::<LoadDesignEvent>()),
+                    random_design.run_if(on_event::<RandomDesignEvent>()),
+                    apply_design.run_if(on_event::<ApplyDesignEvent>()),
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Range of lobes the random designs pick from, fewer look plain and more
+/// blur into a disc.
+const RANDOM_LOBES: std::ops::RangeInclusive<u32> = 3..=40;
+/// Most turns a random design takes to close.
+const RANDOM_MAX_TURNS: u32 = 20;
+
+#[derive(Event, Default)]
+pub struct SaveDesignEvent;
+
+#[derive(Event, Default)]
+pub struct LoadDesignEvent;
+
+#[derive(Event, Default)]
+pub struct RandomDesignEvent;
+
+/// Starts a new drawing with the design.
+#[derive(Event)]
+pub struct ApplyDesignEvent(pub Design);
+
+#[derive(Resource)]
+pub struct PresetsConfig {
+    /// Saved as JSON with a `.json` extension, RON otherwise.
+    pub path: String,
+    pub status: String,
+}
+
+impl Default for PresetsConfig {
+    fn default() -> Self {
+        Self {
+            path: String::from("spirographe.ron"),
+            status: String::new(),
+        }
+    }
+}
+
+/// Everything needed to draw a curve again, without what was drawn.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Design {
+    pub mode: SpirographeMode,
+    pub ring_radius: f32,
+    pub gears: Gears,
+    pub pens: Vec<Pen>,
+    /// Missing from designs saved before the curves drawn without gears.
+    #[serde(default)]
+    pub harmonograph: Harmonograph,
+    #[serde(default)]
+    pub parametric: ParametricCurves,
+    pub speed: f32,
+    pub hue_speed: f32,
+}
+
+impl Design {
+    pub fn capture(spirographe: &Spirographe) -> Self {
+        Self {
+            mode: spirographe.mode,
+            ring_radius: spirographe.ring_radius,
+            gears: spirographe.gears.clone(),
+            pens: spirographe.pens.clone(),
+            harmonograph: spirographe.harmonograph.clone(),
+            parametric: spirographe.parametric.clone(),
+            speed: spirographe.speed,
+            hue_speed: spirographe.hue_speed,
+        }
+    }
+
+    /// Starts a new drawing with the design, fixing what a hand-edited file
+    /// could have broken.
+    pub fn build(&self) -> Spirographe {
+        let mut gears = self.gears.clone();
+        gears.ring_teeth = gears.ring_teeth.max(MIN_TEETH);
+        gears.wheel_teeth = gears.wheel_teeth.max(MIN_TEETH);
+        for gear in &mut gears.chain {
+            gear.teeth = gear.teeth.max(MIN_TEETH);
+        }
+        // Gears too small to hold their wheels fall back to the default ones
+        if !gears.clamp_teeth(self.mode) {
+            gears = Gears::default();
+        }
+
+        let mut pens = self.pens.clone();
+        if pens.is_empty() {
+            pens.push(Pen::default());
+        }
+        for pen in &mut pens {
+            pen.colors.sort_palette();
+        }
+
+        let mut spirographe = Spirographe::new(self.mode, gears, pens);
+        spirographe.speed = self.speed;
+        spirographe.hue_speed = self.hue_speed;
+        spirographe.set_ring_radius(self.ring_radius.max(1.0));
+        spirographe.set_harmonograph(self.harmonograph.clone());
+        spirographe.set_parametric(self.parametric.clone());
+
+        spirographe
+    }
+}
+
+fn design(mode: SpirographeMode, gears: Gears, pens: Vec<Pen>) -> Design {
+    Design {
+        mode,
+        ring_radius: DEFAULT_RING_RADIUS,
+        gears,
+        pens,
+        harmonograph: Harmonograph::default(),
+        parametric: ParametricCurves::default(),
+        speed: SPINNING_CIRCLE_SPEED,
+        hue_speed: HUE_CHANGIN_SPEED,
+    }
+}
+
+fn gears(ring_teeth: u32, wheel_teeth: u32) -> Gears {
+    Gears {
+        ring_teeth,
+        wheel_teeth,
+        chain: Vec::new(),
+        ring_shape: GearShape::Circle,
+        wheel_shape: GearShape::Circle,
+    }
+}
+
+fn pen(hole: u32, phase: f32, colors: ColorScheme) -> Pen {
+    Pen {
+        hole,
+        phase,
+        colors,
+    }
+}
+
+fn rainbow(hue_offset: f32) -> ColorScheme {
+    ColorScheme {
+        hue_offset,
+        ..Default::default()
+    }
+}
+
+fn palette(mode: ColorMode, colors: &[[f32; 3]]) -> ColorScheme {
+    let last = (colors.len() - 1).max(1) as f32;
+
+    ColorScheme {
+        mode,
+        palette: colors
+            .iter()
+            .enumerate()
+            .map(|(idx, color)| ColorStop {
+                position: idx as f32 / last,
+                color: *color,
+            })
+            .collect(),
+        ..Default::default()
+    }
+}
+
+/// Designs shown in the gallery.
+pub fn builtin_presets() -> Vec<(&'static str, Design)> {
+    vec![
+        (
+            "Classic",
+            design(
+                SpirographeMode::Hypotrochoid,
+                gears(96, 52),
+                vec![pen(2, 0.0, rainbow(0.0))],
+            ),
+        ),
+        (
+            "Star",
+            design(
+                SpirographeMode::Hypotrochoid,
+                gears(105, 42),
+                vec![pen(0, 0.0, rainbow(200.0))],
+            ),
+        ),
+        (
+            "Lace",
+            design(
+                SpirographeMode::Hypotrochoid,
+                gears(96, 84),
+                vec![pen(
+                    1,
+                    0.0,
+                    palette(
+                        ColorMode::Distance,
+                        &[[0.1, 0.1, 0.5], [0.3, 0.7, 1.0], [1.0, 1.0, 1.0]],
+                    ),
+                )],
+            ),
+        ),
+        (
+            "Flower",
+            design(
+                SpirographeMode::Epitrochoid,
+                gears(96, 24),
+                vec![pen(
+                    1,
+                    0.0,
+                    palette(
+                        ColorMode::Curvature,
+                        &[[1.0, 0.9, 0.3], [1.0, 0.4, 0.1], [0.6, 0.0, 0.2]],
+                    ),
+                )],
+            ),
+        ),
+        (
+            "Twins",
+            design(
+                SpirographeMode::Hypotrochoid,
+                gears(105, 63),
+                vec![pen(3, 0.0, rainbow(0.0)), pen(3, 180.0, rainbow(180.0))],
+            ),
+        ),
+        (
+            "Sunset",
+            design(
+                SpirographeMode::Hypotrochoid,
+                gears(105, 80),
+                vec![pen(
+                    2,
+                    0.0,
+                    palette(
+                        ColorMode::ArcLength,
+                        &[[0.2, 0.1, 0.4], [0.9, 0.3, 0.4], [1.0, 0.8, 0.3]],
+                    ),
+                )],
+            ),
+        ),
+        (
+            "Nested",
+            design(
+                SpirographeMode::Hypotrochoid,
+                Gears {
+                    chain: vec![ChainGear {
+                        teeth: 24,
+                        outside: false,
+                        reversed: true,
+                    }],
+                    ..gears(96, 60)
+                },
+                vec![pen(
+                    0,
+                    0.0,
+                    palette(
+                        ColorMode::Lap,
+                        &[[0.2, 0.8, 0.6], [0.2, 0.4, 0.9], [0.8, 0.3, 0.9]],
+                    ),
+                )],
+            ),
+        ),
+        (
+            "Pendulums",
+            Design {
+                harmonograph: Harmonograph {
+                    rotary: Some(Pendulum {
+                        frequency: 1.003,
+                        phase: 0.0,
+                        amplitude: 0.25,
+                        decay: 0.004,
+                    }),
+                    ..Default::default()
+                },
+                ..design(
+                    SpirographeMode::Harmonograph,
+                    gears(96, 52),
+                    vec![pen(
+                        0,
+                        0.0,
+                        palette(
+                            ColorMode::ArcLength,
+                            &[[0.9, 0.8, 0.5], [0.8, 0.3, 0.2], [0.2, 0.2, 0.5]],
+                        ),
+                    )],
+                )
+            },
+        ),
+        (
+            "Oval",
+            design(
+                SpirographeMode::Hypotrochoid,
+                Gears {
+                    ring_shape: GearShape::Ellipse { aspect: 1.3 },
+                    ..gears(96, 40)
+                },
+                vec![pen(1, 0.0, rainbow(40.0))],
+            ),
+        ),
+        (
+            "Pebble",
+            design(
+                SpirographeMode::Epitrochoid,
+                Gears {
+                    wheel_shape: GearShape::Convex {
+                        points: vec![
+                            [-1.0, -0.6],
+                            [0.4, -0.9],
+                            [1.1, 0.0],
+                            [0.5, 0.9],
+                            [-0.6, 0.8],
+                        ],
+                    },
+                    ..gears(96, 45)
+                },
+                vec![pen(0, 0.0, rainbow(280.0))],
+            ),
+        ),
+    ]
+}
+
+/// Picks gears drawing a curve with a fair number of lobes that closes
+/// quickly enough, and a few pens with matching colours.
+fn generate_design(catalogue: &GearCatalogue, rng: &mut impl Rng) -> Design {
+    let mode = if rng.random_bool(0.7) {
+        SpirographeMode::Hypotrochoid
+    } else {
+        SpirographeMode::Epitrochoid
+    };
+
+    let ring_teeth = *catalogue.rings.choose(rng).unwrap_or(&96);
+    let candidates: Vec<u32> = catalogue
+        .wheels
+        .iter()
+        .copied()
+        .filter(|wheel_teeth| mode != SpirographeMode::Hypotrochoid || *wheel_teeth < ring_teeth)
+        .filter(|wheel_teeth| {
+            curve::period(mode, &[ring_teeth, *wheel_teeth]).is_some_and(|period| {
+                RANDOM_LOBES.contains(&period.lobes) && period.turns <= RANDOM_MAX_TURNS
+            })
+        })
+        .collect();
+    let wheel_teeth = *candidates
+        .choose(rng)
+        .or(catalogue.wheels.first())
+        .unwrap_or(&52);
+
+    let gears = gears(ring_teeth, wheel_teeth);
+    let hole_count = gears.hole_count();
+
+    let pen_count = if rng.random_bool(0.3) { 2 } else { 1 };
+    let base_hue = rng.random_range(0.0..360.0);
+    let mode_colors = *ColorMode::ALL.choose(rng).unwrap();
+
+    let pens = (0..pen_count)
+        .map(|idx| {
+            let hue = base_hue + idx as f32 * 150.0;
+            let colors = match mode_colors {
+                ColorMode::Rainbow => rainbow(hue % 360.0),
+                ColorMode::Solid => ColorScheme {
+                    mode: ColorMode::Solid,
+                    solid: Color::hsv(hue % 360.0, 0.7, 1.0)
+                        .to_srgba()
+                        .to_f32_array_no_alpha(),
+                    ..Default::default()
+                },
+                mode => palette(
+                    mode,
+                    &[0.0, 40.0, 80.0].map(|step| {
+                        Color::hsv((hue + step) % 360.0, 0.8, 1.0)
+                            .to_srgba()
+                            .to_f32_array_no_alpha()
+                    }),
+                ),
+            };
+
+            pen(rng.random_range(0..hole_count), idx as f32 * 180.0, colors)
+        })
+        .collect();
+
+    Design {
+        hue_speed: rng.random_range(5.0..40.0),
+        ..design(mode, gears, pens)
+    }
+}
+
+fn write_design(path: &str, design: &Design) -> io::Result<()> {
+    let content = if is_json(path) {
+        serde_json::to_string_pretty(design).map_err(io::Error::other)?
+    } else {
+        ron::ser::to_string_pretty(design, ron::ser::PrettyConfig::default())
+            .map_err(io::Error::other)?
+    };
+
+    fs::write(path, content)
+}
+
+fn read_design(path: &str) -> io::Result<Design> {
+    let content = fs::read_to_string(path)?;
+
+    if is_json(path) {
+        serde_json::from_str(&content).map_err(io::Error::other)
+    } else {
+        ron::from_str(&content).map_err(io::Error::other)
+    }
+}
+
+fn is_json(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("json"))
+}
+
+fn save_design(spirographe: Res<Spirographe>, mut config: ResMut<PresetsConfig>) {
+    config.status = match write_design(&config.path, &Design::capture(&spirographe)) {
+        Ok(()) => format!("Saved {}", config.path),
+        Err(e) => format!("Save failed: {e}"),
+    };
+}
+
+fn load_design(
+    mut config: ResMut<PresetsConfig>,
+    mut apply_design_event: EventWriter<ApplyDesignEvent>,
+) {
+    match read_design(&config.path) {
+        Ok(design) => {
+            config.status = format!("Loaded {}", config.path);
+            apply_design_event.send(ApplyDesignEvent(design));
+        }
+        Err(e) => config.status = format!("Load failed: {e}"),
+    }
+}
+
+fn random_design(
+    catalogue: Res<GearCatalogue>,
+    mut apply_design_event: EventWriter<ApplyDesignEvent>,
+) {
+    let design = generate_design(&catalogue, &mut rand::rng());
+    apply_design_event.send(ApplyDesignEvent(design));
+}
+
+fn apply_design(mut commands: Commands, mut apply_design_event: EventReader<ApplyDesignEvent>) {
+    if let Some(ApplyDesignEvent(design)) = apply_design_event.read().last() {
+        commands.insert_resource(design.build());
+    }
+}
@@ -0,0 +1,286 @@
+use std::f64::consts::{PI, TAU};
+
+use bevy::math::DVec2;
+use serde::{Deserialize, Serialize};
+
+/// Points an outline is measured along before being resampled.
+const DENSE_SAMPLES: usize = 4096;
+/// Points kept along an outline, at even distances.
+const OUTLINE_SAMPLES: usize = 1024;
+/// Times the corners of a custom shape are cut, rounding them enough to roll over.
+const SMOOTHING_PASSES: usize = 4;
+/// Least rounding of the polygons, a wheel can't roll over a sharp corner
+/// without slipping.
+pub const MIN_ROUNDING: f32 = 0.05;
+
+/// Outline of a ring or a wheel.
+///
+/// Whatever the shape, it's scaled to be as long as the circle of the gear's
+/// radius, so it has as many teeth.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum GearShape {
+    #[default]
+    Circle,
+    /// As wide as `aspect` times its height.
+    Ellipse { aspect: f32 },
+    /// Regular polygon, its corners rounded by `rounding` of its inner radius.
+    Polygon { sides: u32, rounding: f32 },
+    /// Convex hull of the points, its corners rounded.
+    Convex { points: Vec<[f32; 2]> },
+}
+
+impl GearShape {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GearShape::Circle => "Circle",
+            GearShape::Ellipse { .. } => "Ellipse",
+            GearShape::Polygon { .. } => "Polygon",
+            GearShape::Convex { .. } => "Custom",
+        }
+    }
+
+    /// Outline of the shape for a gear of radius 1, `None` for the circle
+    /// which is rolled exactly.
+    pub fn outline(&self) -> Option<Outline> {
+        let points: Vec<DVec2> = match self {
+            GearShape::Circle => return None,
+            GearShape::Ellipse { aspect } => {
+                let aspect = aspect.max(0.05) as f64;
+                (0..DENSE_SAMPLES)
+                    .map(|idx| {
+                        let angle = TAU * idx as f64 / DENSE_SAMPLES as f64;
+                        DVec2::new(aspect * angle.cos(), angle.sin())
+                    })
+                    .collect()
+            }
+            GearShape::Polygon { sides, rounding } => {
+                rounded_polygon(*sides, rounding.clamp(MIN_ROUNDING, 1.0) as f64)
+            }
+            GearShape::Convex { points } => {
+                let points: Vec<DVec2> = points
+                    .iter()
+                    .map(|[x, y]| DVec2::new(*x as f64, *y as f64))
+                    .collect();
+                smooth(&convex_hull(&points))
+            }
+        };
+
+        Outline::new(&points)
+    }
+
+    /// Distance from the center to the closest point of the outline, for
+    /// a gear of radius 1, the pencil holes staying within it.
+    pub fn reach(&self) -> f32 {
+        self.outline().map_or(1.0, |outline| outline.reach() as f32)
+    }
+}
+
+/// Closed convex outline going counterclockwise around the center of the
+/// gear, `TAU` long.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outline {
+    /// Points at even distances along the outline.
+    points: Vec<DVec2>,
+    /// Direction of the outline at each point, growing by `TAU` over a lap.
+    tangents: Vec<f64>,
+}
+
+impl Outline {
+    /// Resamples the outline through `points` and scales it to be `TAU` long,
+    /// centered on the average of the points.
+    fn new(points: &[DVec2]) -> Option<Self> {
+        if points.len() < 3 {
+            return None;
+        }
+
+        let center = points.iter().sum::<DVec2>() / points.len() as f64;
+        let closed: Vec<DVec2> = points
+            .iter()
+            .chain(points.first())
+            .map(|point| *point - center)
+            .collect();
+
+        let lengths: Vec<f64> = closed
+            .windows(2)
+            .scan(0.0, |length, pair| {
+                *length += pair[0].distance(pair[1]);
+                Some(*length)
+            })
+            .collect();
+        let perimeter = *lengths.last()?;
+        if perimeter <= f64::EPSILON {
+            return None;
+        }
+        let scale = TAU / perimeter;
+
+        let mut segment = 0;
+        let points: Vec<DVec2> = (0..OUTLINE_SAMPLES)
+            .map(|idx| {
+                let along = perimeter * idx as f64 / OUTLINE_SAMPLES as f64;
+                while lengths[segment] < along {
+                    segment += 1;
+                }
+
+                let start = if segment == 0 {
+                    0.0
+                } else {
+                    lengths[segment - 1]
+                };
+                let fraction = (along - start) / (lengths[segment] - start).max(f64::EPSILON);
+                closed[segment].lerp(closed[segment + 1], fraction) * scale
+            })
+            .collect();
+
+        // Unwrapped, so they can be interpolated across the jump at ±PI
+        let mut tangents: Vec<f64> = Vec::with_capacity(OUTLINE_SAMPLES);
+        for idx in 0..OUTLINE_SAMPLES {
+            let next = points[(idx + 1) % OUTLINE_SAMPLES];
+            let previous = points[(idx + OUTLINE_SAMPLES - 1) % OUTLINE_SAMPLES];
+            let angle = (next - previous).to_angle();
+
+            tangents.push(match tangents.last() {
+                Some(last) => last + (angle - last + PI).rem_euclid(TAU) - PI,
+                None => angle,
+            });
+        }
+
+        Some(Self { points, tangents })
+    }
+
+    /// Point and direction of the outline `along` from its start.
+    fn at(&self, along: f64) -> (DVec2, f64) {
+        let step = along.rem_euclid(TAU) / TAU * OUTLINE_SAMPLES as f64;
+        let idx = (step as usize).min(OUTLINE_SAMPLES - 1);
+        let next = (idx + 1) % OUTLINE_SAMPLES;
+        let fraction = step - idx as f64;
+
+        let next_tangent = self.tangents[next] + if next == 0 { TAU } else { 0.0 };
+        let laps = (along / TAU).floor();
+
+        (
+            self.points[idx].lerp(self.points[next], fraction),
+            self.tangents[idx] + (next_tangent - self.tangents[idx]) * fraction + laps * TAU,
+        )
+    }
+
+    fn reach(&self) -> f64 {
+        self.points
+            .iter()
+            .map(|point| point.length())
+            .fold(f64::INFINITY, f64::min)
+    }
+}
+
+/// Point and direction, `along` from its start, of an outline of radius 1,
+/// the circle starting on the right.
+pub fn outline_at(outline: Option<&Outline>, along: f64) -> (DVec2, f64) {
+    match outline {
+        Some(outline) => outline.at(along),
+        None => (DVec2::from_angle(along), along + PI / 2.0),
+    }
+}
+
+/// Center and rotation of a gear of `radius`, in the frame of the one of
+/// `parent_radius` it rolls on, after going `orbit` radians around it.
+///
+/// Rolling without slipping, the gear went along its own outline as far as
+/// it went along its parent's, and turned so both run the same way where
+/// they touch. Rolling outside, the gear runs the other way along its outline.
+pub fn roll_on(
+    parent: Option<&Outline>,
+    parent_radius: f64,
+    gear: Option<&Outline>,
+    radius: f64,
+    outside: bool,
+    orbit: f64,
+) -> (DVec2, f64) {
+    let along = orbit * parent_radius;
+    let (contact, parent_tangent) = outline_at(parent, orbit);
+
+    let (gear_along, turn) = if outside {
+        // Starts touching with the opposite side of the gear
+        (PI * radius - along, PI)
+    } else {
+        (along, 0.0)
+    };
+    let (gear_contact, gear_tangent) = outline_at(gear, gear_along / radius);
+
+    let rotation = parent_tangent + turn - gear_tangent;
+    let center =
+        contact * parent_radius - DVec2::from_angle(rotation).rotate(gear_contact * radius);
+
+    (center, rotation)
+}
+
+/// Regular polygon with a flat bottom, built as a smaller polygon grown by
+/// the radius of the corners.
+fn rounded_polygon(sides: u32, rounding: f64) -> Vec<DVec2> {
+    let sides = sides.max(3);
+    let half_angle = PI / sides as f64;
+    let corner_radius = rounding * half_angle.cos();
+    let inner_radius = 1.0 - rounding;
+
+    let per_corner = DENSE_SAMPLES / sides as usize;
+    (0..sides)
+        .flat_map(|corner| {
+            let direction = -PI / 2.0 + half_angle + 2.0 * half_angle * corner as f64;
+            let vertex = DVec2::from_angle(direction) * inner_radius;
+
+            // The arc covers the turn between both edges of the corner
+            (0..per_corner).map(move |idx| {
+                let normal =
+                    direction - half_angle + 2.0 * half_angle * idx as f64 / per_corner as f64;
+                vertex + DVec2::from_angle(normal) * corner_radius
+            })
+        })
+        .collect()
+}
+
+/// Monotone chain, counterclockwise.
+fn convex_hull(points: &[DVec2]) -> Vec<DVec2> {
+    let mut sorted = points.to_vec();
+    sorted.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    sorted.dedup();
+    if sorted.len() < 3 {
+        return sorted;
+    }
+
+    let mut hull: Vec<DVec2> = Vec::with_capacity(sorted.len() * 2);
+    for pass in [sorted.clone(), sorted.into_iter().rev().collect()] {
+        let start = hull.len();
+        for point in pass {
+            while hull.len() >= start + 2 {
+                let [a, b] = [hull[hull.len() - 2], hull[hull.len() - 1]];
+                if (b - a).perp_dot(point - a) > 0.0 {
+                    break;
+                }
+                hull.pop();
+            }
+            hull.push(point);
+        }
+        // The last point starts the other pass
+        hull.pop();
+    }
+
+    hull
+}
+
+/// Cuts every corner of the closed polygon, Chaikin's way.
+fn smooth(points: &[DVec2]) -> Vec<DVec2> {
+    let mut points = points.to_vec();
+
+    for _ in 0..SMOOTHING_PASSES {
+        if points.len() < 3 {
+            break;
+        }
+
+        points = (0..points.len())
+            .flat_map(|idx| {
+                let [a, b] = [points[idx], points[(idx + 1) % points.len()]];
+                [a.lerp(b, 0.25), a.lerp(b, 0.75)]
+            })
+            .collect();
+    }
+
+    points
+}
@@ -0,0 +1,307 @@
+use bevy::math::Vec2;
+
+/// Segments each Bézier curve is flattened into.
+const CURVE_SEGMENTS: usize = 16;
+
+/// `d` attribute of the first `<path>` of an SVG document.
+pub fn first_path_data(svg: &str) -> Option<&str> {
+    let path = &svg[svg.find("<path")?..];
+    let path = &path[..path.find('>')?];
+
+    // Skip attributes merely ending in d, like `id`
+    path.match_indices('d')
+        .filter(|(idx, _)| *idx > 0 && path.as_bytes()[idx - 1].is_ascii_whitespace())
+        .find_map(|(idx, _)| {
+            let value = path[idx + 1..].trim_start().strip_prefix('=')?.trim_start();
+            let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+            let data = &value[1..];
+            Some(&data[..data.find(quote)?])
+        })
+}
+
+/// Points along the first subpath of a path's data, with y pointing up.
+///
+/// Lines are kept as their ends, curves are flattened and arcs are replaced
+/// by a line to where they end.
+pub fn flatten(data: &str) -> Result<Vec<Vec2>, String> {
+    let mut tokens = Tokens::new(data);
+    let mut points: Vec<Vec2> = Vec::new();
+
+    let mut current = Vec2::ZERO;
+    let mut start = Vec2::ZERO;
+    // Last control point of the previous command when it was a cubic or
+    // a quadratic curve, reflected by the smooth curves of the same kind
+    let mut last_cubic: Option<Vec2> = None;
+    let mut last_quadratic: Option<Vec2> = None;
+    let mut command = None;
+
+    loop {
+        tokens.skip_separators();
+        if let Some(letter) = tokens.command() {
+            // A second subpath ends the first one
+            if letter.eq_ignore_ascii_case(&'m') && !points.is_empty() {
+                break;
+            }
+            command = Some(letter);
+        } else if tokens.is_done() {
+            break;
+        }
+
+        let Some(letter) = command else {
+            return Err(String::from("expected a path command"));
+        };
+        let relative = letter.is_ascii_lowercase();
+        let origin = if relative { current } else { Vec2::ZERO };
+
+        let (mut cubic, mut quadratic) = (None, None);
+        match letter.to_ascii_uppercase() {
+            'M' => {
+                current = origin + tokens.point()?;
+                start = current;
+                points.push(current);
+                // Further pairs are implicit lines
+                command = Some(if relative { 'l' } else { 'L' });
+            }
+            'L' => {
+                current = origin + tokens.point()?;
+                points.push(current);
+            }
+            'H' => {
+                current.x = origin.x + tokens.number()?;
+                points.push(current);
+            }
+            'V' => {
+                current.y = origin.y + tokens.number()?;
+                points.push(current);
+            }
+            'C' | 'S' => {
+                let first = if letter.eq_ignore_ascii_case(&'c') {
+                    origin + tokens.point()?
+                } else {
+                    last_cubic.map_or(current, |control| 2.0 * current - control)
+                };
+                let second = origin + tokens.point()?;
+                let end = origin + tokens.point()?;
+
+                push_curve(&mut points, |along| {
+                    let rest = 1.0 - along;
+                    rest * rest * rest * current
+                        + 3.0 * rest * rest * along * first
+                        + 3.0 * rest * along * along * second
+                        + along * along * along * end
+                });
+
+                cubic = Some(second);
+                current = end;
+            }
+            'Q' | 'T' => {
+                let middle = if letter.eq_ignore_ascii_case(&'q') {
+                    origin + tokens.point()?
+                } else {
+                    last_quadratic.map_or(current, |control| 2.0 * current - control)
+                };
+                let end = origin + tokens.point()?;
+
+                push_curve(&mut points, |along| {
+                    let rest = 1.0 - along;
+                    rest * rest * current + 2.0 * rest * along * middle + along * along * end
+                });
+
+                quadratic = Some(middle);
+                current = end;
+            }
+            'A' => {
+                for _ in 0..5 {
+                    tokens.number()?;
+                }
+                current = origin + tokens.point()?;
+                points.push(current);
+            }
+            'Z' => {
+                current = start;
+                command = None;
+            }
+            other => return Err(format!("unknown path command {other}")),
+        }
+
+        (last_cubic, last_quadratic) = (cubic, quadratic);
+    }
+
+    if points.len() < 2 {
+        return Err(String::from("the path has no length"));
+    }
+
+    Ok(points
+        .into_iter()
+        .map(|point| Vec2::new(point.x, -point.y))
+        .collect())
+}
+
+fn push_curve(points: &mut Vec<Vec2>, at: impl Fn(f32) -> Vec2) {
+    points.extend((1..=CURVE_SEGMENTS).map(|step| at(step as f32 / CURVE_SEGMENTS as f32)));
+}
+
+/// Commands and numbers of path data, which can be separated by spaces,
+/// commas, or nothing at all when a sign or a second dot starts the next number.
+struct Tokens<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(data: &'a str) -> Self {
+        Self { rest: data }
+    }
+
+    fn is_done(&self) -> bool {
+        self.rest.is_empty()
+    }
+
+    fn skip_separators(&mut self) {
+        self.rest = self
+            .rest
+            .trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+    }
+
+    fn command(&mut self) -> Option<char> {
+        let letter = self.rest.chars().next()?;
+        // `e` only appears inside numbers
+        if !letter.is_ascii_alphabetic() || letter.eq_ignore_ascii_case(&'e') {
+            return None;
+        }
+
+        self.rest = &self.rest[1..];
+        Some(letter)
+    }
+
+    fn number(&mut self) -> Result<f32, String> {
+        self.skip_separators();
+
+        let bytes = self.rest.as_bytes();
+        let mut end = 0;
+        let mut seen_dot = false;
+        let mut seen_exponent = false;
+
+        while end < bytes.len() {
+            let byte = bytes[end];
+            let previous = end.checked_sub(1).map(|idx| bytes[idx]);
+
+            let belongs = match byte {
+                b'0'..=b'9' => true,
+                b'+' | b'-' => end == 0 || matches!(previous, Some(b'e' | b'E')),
+                b'.' if !seen_dot && !seen_exponent => {
+                    seen_dot = true;
+                    true
+                }
+                b'e' | b'E' if end > 0 && !seen_exponent => {
+                    seen_exponent = true;
+                    true
+                }
+                _ => false,
+            };
+            if !belongs {
+                break;
+            }
+
+            end += 1;
+        }
+
+        let (number, rest) = self.rest.split_at(end);
+        let value = number.parse().map_err(|_| {
+            let context: String = self.rest.chars().take(20).collect();
+            format!("expected a number at \"{context}\"")
+        })?;
+
+        self.rest = rest;
+        Ok(value)
+    }
+
+    fn point(&mut self) -> Result<Vec2, String> {
+        Ok(Vec2::new(self.number()?, self.number()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flat(data: &str) -> Vec<[f32; 2]> {
+        flatten(data)
+            .unwrap()
+            .into_iter()
+            .map(|point| point.to_array())
+            .collect()
+    }
+
+    #[test]
+    fn finds_data_attribute() {
+        let svg = r#"<svg><path id="a" d="M 0 0 L 1 1"/></svg>"#;
+        assert_eq!(first_path_data(svg), Some("M 0 0 L 1 1"));
+
+        let svg = "<path\n  d = 'M0 0h1' />";
+        assert_eq!(first_path_data(svg), Some("M0 0h1"));
+    }
+
+    #[test]
+    fn rejects_unquoted_data() {
+        assert_eq!(first_path_data("<path d=é0/>"), None);
+        assert_eq!(first_path_data("<path d=M0,0/>"), None);
+        assert_eq!(first_path_data(r#"<path id="d"/>"#), None);
+    }
+
+    #[test]
+    fn implicit_lines_after_move() {
+        assert_eq!(flat("M 0 0 1 0 1 1"), [[0.0, 0.0], [1.0, 0.0], [1.0, -1.0]]);
+        assert_eq!(
+            flat("m 1 1 2 0 0 2"),
+            [[1.0, -1.0], [3.0, -1.0], [3.0, -3.0]]
+        );
+    }
+
+    #[test]
+    fn relative_commands() {
+        assert_eq!(
+            flat("M 1 1 l 2 0 h 1 v 2 L 0 0"),
+            [
+                [1.0, -1.0],
+                [3.0, -1.0],
+                [4.0, -1.0],
+                [4.0, -3.0],
+                [0.0, 0.0]
+            ]
+        );
+    }
+
+    #[test]
+    fn packed_numbers() {
+        assert_eq!(flat("M1.5.5-3e-2.25"), [[1.5, -0.5], [-0.03, -0.25]]);
+    }
+
+    #[test]
+    fn second_subpath_ends_the_first() {
+        assert_eq!(
+            flat("M 0 0 L 1 0 L 1 1 Z M 5 5 L 6 6"),
+            [[0.0, 0.0], [1.0, 0.0], [1.0, -1.0]]
+        );
+        assert_eq!(flat("M 0 0 L 1 0 z m 5 5 l 1 1"), [[0.0, 0.0], [1.0, 0.0]]);
+    }
+
+    #[test]
+    fn smooth_curves_only_reflect_their_own_kind() {
+        // Without a matching control point to reflect, they start straight
+        // from the current point and stay on the x axis
+        let after_quadratic = flat("M 0 0 Q 1 1 2 0 S 4 0 4 0");
+        assert!(after_quadratic[CURVE_SEGMENTS + 1..]
+            .iter()
+            .all(|point| point[1] == 0.0));
+
+        let after_cubic = flat("M 0 0 C 0 1 2 1 2 0 T 4 0");
+        assert!(after_cubic[CURVE_SEGMENTS + 1..]
+            .iter()
+            .all(|point| point[1] == 0.0));
+
+        let after_same_kind = flat("M 0 0 Q 1 1 2 0 T 4 0");
+        assert!(after_same_kind[CURVE_SEGMENTS + 1..]
+            .iter()
+            .any(|point| point[1] != 0.0));
+    }
+}
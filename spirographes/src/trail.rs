@@ -0,0 +1,525 @@
+use std::f32::consts::PI;
+
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, PrimitiveTopology, VertexAttributeValues},
+        render_asset::RenderAssetUsages,
+    },
+};
+
+use crate::{Spirographe, SpirographeMesh, Trace};
+
+pub struct TrailPlugin;
+
+impl Plugin for TrailPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TrailStyle>()
+            .add_systems(PostUpdate, (scale_feather, update_trail).chain());
+    }
+}
+
+/// Points triangulated into each mesh of the trail. Only the last mesh is
+/// changed as the curve grows, so this bounds what is uploaded every frame.
+const CHUNK_POINTS: usize = 4096;
+/// Longest a miter can get, in line widths, before it is cut short.
+const MITER_LIMIT: f32 = 4.0;
+/// Vertices around the circle drawn on each point by round joins.
+const ROUND_JOIN_SEGMENTS: u32 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineJoin {
+    Miter,
+    Round,
+}
+
+#[derive(Resource, Clone, Copy, PartialEq)]
+pub struct TrailStyle {
+    /// Width of the line, in world units.
+    pub width: f32,
+    pub antialiasing: bool,
+    pub join: LineJoin,
+    /// Width of the fading edge added on both sides of anti-aliased lines, in
+    /// world units. Follows the zoom so it stays about a pixel on screen.
+    pub feather: f32,
+}
+
+impl Default for TrailStyle {
+    fn default() -> Self {
+        Self {
+            width: 2.0,
+            antialiasing: true,
+            join: LineJoin::Miter,
+            feather: 1.0,
+        }
+    }
+}
+
+impl TrailStyle {
+    /// Offsets across the line of the vertices drawn for each side of a point,
+    /// from the outside in, with their opacity.
+    fn edges(&self) -> Vec<(f32, f32)> {
+        let half_width = self.width / 2.0;
+
+        if self.antialiasing {
+            vec![
+                (-half_width - self.feather, 0.0),
+                (-half_width, 1.0),
+                (half_width, 1.0),
+                (half_width + self.feather, 0.0),
+            ]
+        } else {
+            vec![(-half_width, 1.0), (half_width, 1.0)]
+        }
+    }
+
+    /// Every point gets the same number of vertices, so the mesh can be cut
+    /// back to any point.
+    fn vertices_per_point(&self) -> usize {
+        let row = self.edges().len();
+
+        match self.join {
+            LineJoin::Miter => row,
+            // Segment from the previous point, then a disc with its fading ring
+            LineJoin::Round => 2 * row + self.disc_vertices(),
+        }
+    }
+
+    /// Indices of the triangles drawn for the first `point_count` points.
+    fn index_count(&self, point_count: usize) -> usize {
+        let band = 6 * (self.edges().len() - 1);
+
+        match self.join {
+            // The first point only starts the line
+            LineJoin::Miter => point_count.saturating_sub(1) * band,
+            LineJoin::Round => {
+                let disc = if self.antialiasing { 9 } else { 3 } * ROUND_JOIN_SEGMENTS as usize;
+                point_count * (band + disc)
+            }
+        }
+    }
+
+    fn disc_vertices(&self) -> usize {
+        let rings = if self.antialiasing { 2 } else { 1 };
+        1 + rings * ROUND_JOIN_SEGMENTS as usize
+    }
+}
+
+/// Triangulated line drawn through the curve points, split into chunks
+/// spawned as children of the entity.
+#[derive(Component)]
+pub struct Trail {
+    material: Handle<ColorMaterial>,
+    chunks: Vec<TrailChunk>,
+    /// Points already triangulated into the chunks.
+    drawn_points: usize,
+    /// Style the chunks were built with.
+    style: Option<TrailStyle>,
+}
+
+impl Trail {
+    pub fn new(material: Handle<ColorMaterial>) -> Self {
+        Self {
+            material,
+            chunks: Vec::new(),
+            drawn_points: 0,
+            style: None,
+        }
+    }
+}
+
+struct TrailChunk {
+    entity: Entity,
+    mesh: Handle<Mesh>,
+    /// Index of the first point triangulated in this chunk.
+    first_point: usize,
+}
+
+fn create_trail_mesh() -> Mesh {
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD,
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, Vec::<[f32; 3]>::new())
+    .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, Vec::<[f32; 4]>::new())
+    .with_inserted_indices(Indices::U32(Vec::new()))
+}
+
+/// Vertices and triangles of a trail chunk, borrowed from its mesh.
+struct Geometry {
+    /// Index of the point the first vertices are drawn for.
+    first_point: usize,
+    positions: Vec<[f32; 3]>,
+    colors: Vec<[f32; 4]>,
+    indices: Vec<u32>,
+}
+
+impl Geometry {
+    fn take(mesh: &mut Mesh, first_point: usize) -> Self {
+        let positions = match mesh.remove_attribute(Mesh::ATTRIBUTE_POSITION) {
+            Some(VertexAttributeValues::Float32x3(positions)) => positions,
+            _ => Vec::new(),
+        };
+        let colors = match mesh.remove_attribute(Mesh::ATTRIBUTE_COLOR) {
+            Some(VertexAttributeValues::Float32x4(colors)) => colors,
+            _ => Vec::new(),
+        };
+        let indices = match mesh.remove_indices() {
+            Some(Indices::U32(indices)) => indices,
+            _ => Vec::new(),
+        };
+
+        Self {
+            first_point,
+            positions,
+            colors,
+            indices,
+        }
+    }
+
+    fn put_back(self, mesh: &mut Mesh) {
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, self.positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, self.colors);
+        mesh.insert_indices(Indices::U32(self.indices));
+    }
+
+    /// Cuts the geometry back to the points before `end`.
+    fn truncate(&mut self, end: usize, style: &TrailStyle) {
+        let point_count = end - self.first_point;
+        let vertex_count = point_count * style.vertices_per_point();
+
+        self.positions.truncate(vertex_count);
+        self.colors.truncate(vertex_count);
+        self.indices.truncate(style.index_count(point_count));
+    }
+
+    fn push_vertex(&mut self, position: Vec2, color: [f32; 4], opacity: f32) -> u32 {
+        self.positions.push([position.x, position.y, 0.0]);
+        self.colors
+            .push([color[0], color[1], color[2], color[3] * opacity]);
+        self.positions.len() as u32 - 1
+    }
+
+    /// Quads between two rows of vertices laid out across the line.
+    fn push_band(&mut self, from_row: u32, to_row: u32, row_len: u32) {
+        for i in 0..row_len - 1 {
+            self.indices.extend_from_slice(&[
+                from_row + i,
+                from_row + i + 1,
+                to_row + i + 1,
+                from_row + i,
+                to_row + i + 1,
+                to_row + i,
+            ]);
+        }
+    }
+
+    /// Row of vertices for point `i`, offset along the miter of its two segments.
+    ///
+    /// Only the points up to `end` are considered drawn.
+    fn miter_row(points: &[[f32; 3]], i: usize, end: usize, style: &TrailStyle) -> Vec<Vec2> {
+        let point = position(points, i);
+        let before = i
+            .checked_sub(1)
+            .and_then(|prev| (point - position(points, prev)).try_normalize());
+        let after = (i + 1 < end)
+            .then(|| (position(points, i + 1) - point).try_normalize())
+            .flatten();
+
+        let (normal, scale) = match (before, after) {
+            (Some(before), Some(after)) => {
+                let normal = (before.perp() + after.perp())
+                    .try_normalize()
+                    .unwrap_or(before.perp());
+                let scale = 1.0 / normal.dot(before.perp()).max(1.0 / MITER_LIMIT);
+                (normal, scale)
+            }
+            (Some(direction), None) | (None, Some(direction)) => (direction.perp(), 1.0),
+            (None, None) => (Vec2::Y, 1.0),
+        };
+
+        style
+            .edges()
+            .iter()
+            .map(|(offset, _)| point + normal * *offset * scale)
+            .collect()
+    }
+
+    /// Moves the row of point `i` to follow the points up to `end`.
+    fn update_miter_row(&mut self, points: &[[f32; 3]], i: usize, end: usize, style: &TrailStyle) {
+        let first_vertex = (i - self.first_point) * style.vertices_per_point();
+
+        for (j, position) in Self::miter_row(points, i, end, style)
+            .into_iter()
+            .enumerate()
+        {
+            self.positions[first_vertex + j] = [position.x, position.y, 0.0];
+        }
+    }
+
+    fn push_miter_point(
+        &mut self,
+        points: &[[f32; 3]],
+        colors: &[[f32; 4]],
+        i: usize,
+        style: &TrailStyle,
+    ) {
+        let edges = style.edges();
+        let row_len = edges.len() as u32;
+
+        // The previous row was an end, it now bends towards this point
+        if i > self.first_point {
+            self.update_miter_row(points, i - 1, i + 1, style);
+        }
+
+        let row = self.positions.len() as u32;
+        for (position, (_, opacity)) in Self::miter_row(points, i, i + 1, style)
+            .into_iter()
+            .zip(&edges)
+        {
+            self.push_vertex(position, colors[i], *opacity);
+        }
+
+        if i > self.first_point {
+            self.push_band(row - row_len, row, row_len);
+        }
+    }
+
+    fn push_point(
+        &mut self,
+        points: &[[f32; 3]],
+        colors: &[[f32; 4]],
+        i: usize,
+        style: &TrailStyle,
+    ) {
+        match style.join {
+            LineJoin::Miter => self.push_miter_point(points, colors, i, style),
+            LineJoin::Round => self.push_round_point(points, colors, i, style),
+        }
+    }
+
+    fn push_round_point(
+        &mut self,
+        points: &[[f32; 3]],
+        colors: &[[f32; 4]],
+        i: usize,
+        style: &TrailStyle,
+    ) {
+        let edges = style.edges();
+        let row_len = edges.len() as u32;
+
+        // Segment from the previous point, even when it is in the previous
+        // chunk, a zero length one for the first point
+        let from = position(points, i.saturating_sub(1));
+        let to = position(points, i);
+        let normal = (to - from).try_normalize().unwrap_or(Vec2::X).perp();
+
+        let from_row = self.positions.len() as u32;
+        for (offset, opacity) in &edges {
+            self.push_vertex(from + normal * *offset, colors[i], *opacity);
+        }
+        let to_row = self.positions.len() as u32;
+        for (offset, opacity) in &edges {
+            self.push_vertex(to + normal * *offset, colors[i], *opacity);
+        }
+        self.push_band(from_row, to_row, row_len);
+
+        // Disc covering the joint
+        let half_width = style.width / 2.0;
+        let center = self.push_vertex(to, colors[i], 1.0);
+        let ring = self.positions.len() as u32;
+
+        let mut radii = vec![(half_width, 1.0)];
+        if style.antialiasing {
+            radii.push((half_width + style.feather, 0.0));
+        }
+
+        for (radius, opacity) in radii {
+            for k in 0..ROUND_JOIN_SEGMENTS {
+                let angle = k as f32 / ROUND_JOIN_SEGMENTS as f32 * 2.0 * PI;
+                self.push_vertex(to + Vec2::from_angle(angle) * radius, colors[i], opacity);
+            }
+        }
+
+        for k in 0..ROUND_JOIN_SEGMENTS {
+            let next = (k + 1) % ROUND_JOIN_SEGMENTS;
+            self.indices
+                .extend_from_slice(&[center, ring + k, ring + next]);
+
+            if style.antialiasing {
+                let outer = ring + ROUND_JOIN_SEGMENTS;
+                self.indices.extend_from_slice(&[
+                    ring + k,
+                    outer + k,
+                    outer + next,
+                    ring + k,
+                    outer + next,
+                    ring + next,
+                ]);
+            }
+        }
+    }
+}
+
+fn position(points: &[[f32; 3]], i: usize) -> Vec2 {
+    Vec2::new(points[i][0], points[i][1])
+}
+
+fn spawn_chunk(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    trail_entity: Entity,
+    trail: &mut Trail,
+    first_point: usize,
+) {
+    let mesh = meshes.add(create_trail_mesh());
+
+    let entity = commands
+        .spawn(ColorMesh2dBundle {
+            mesh: mesh.clone().into(),
+            material: trail.material.clone(),
+            ..Default::default()
+        })
+        .set_parent(trail_entity)
+        .id();
+
+    trail.chunks.push(TrailChunk {
+        entity,
+        mesh,
+        first_point,
+    });
+}
+
+/// Sets the feather to the world size of a pixel, rounded to a power of two
+/// so the trail is only rebuilt when the zoom doubles or halves.
+fn scale_feather(
+    camera: Query<&OrthographicProjection, With<Camera2d>>,
+    mut style: ResMut<TrailStyle>,
+) {
+    let Ok(projection) = camera.get_single() else {
+        return;
+    };
+
+    let feather = projection.scale.log2().round().exp2();
+    if style.feather != feather {
+        style.feather = feather;
+    }
+}
+
+/// Triangulates the points added since the last frame, and redraws from the
+/// first changed one when earlier points were replaced.
+///
+/// Only the chunks holding new or changed points are touched, the others
+/// stay as they were uploaded.
+fn update_trail(
+    mut commands: Commands,
+    mut spirographe: ResMut<Spirographe>,
+    style: Res<TrailStyle>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut trails: Query<(Entity, &SpirographeMesh, &mut Trail)>,
+) {
+    for (trail_entity, key, mut trail) in &mut trails {
+        // Taking the changes isn't one, the trail is only derived from the points
+        let Some(trace) = spirographe
+            .bypass_change_detection()
+            .trace_mut(key.layer, key.pen)
+        else {
+            continue;
+        };
+
+        update_trail_chunks(
+            &mut commands,
+            &mut meshes,
+            &style,
+            trail_entity,
+            &mut trail,
+            trace,
+        );
+    }
+}
+
+/// Rebuilds the chunks holding the points of `trace` that changed.
+fn update_trail_chunks(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    style: &TrailStyle,
+    trail_entity: Entity,
+    trail: &mut Trail,
+    trace: &mut Trace,
+) {
+    let mut first_point = trail.drawn_points.min(trace.points.len());
+    if let Some(dirty_from) = trace.dirty_from.take() {
+        first_point = first_point.min(dirty_from);
+    }
+    if trail.style != Some(*style) {
+        first_point = 0;
+    }
+
+    if first_point == trail.drawn_points && trail.drawn_points == trace.points.len() {
+        return;
+    }
+
+    let points = &trace.points;
+    let colors = &trace.colors;
+
+    // Chunks that only hold changed points are rebuilt from scratch
+    while trail
+        .chunks
+        .last()
+        .is_some_and(|chunk| chunk.first_point >= first_point)
+    {
+        let chunk = trail.chunks.pop().unwrap();
+        commands.entity(chunk.entity).despawn_recursive();
+    }
+
+    if trail.chunks.is_empty() {
+        spawn_chunk(commands, meshes, trail_entity, trail, 0);
+    }
+
+    let chunk = trail.chunks.last().unwrap();
+    let mut geometry = Geometry::take(meshes.get_mut(&chunk.mesh).unwrap(), chunk.first_point);
+    geometry.truncate(first_point, style);
+
+    // Without a point after it anymore, the last row is an end again
+    if style.join == LineJoin::Miter
+        && first_point > geometry.first_point
+        && first_point == points.len()
+    {
+        geometry.update_miter_row(points, first_point - 1, first_point, style);
+    }
+
+    for i in first_point..points.len() {
+        if i - geometry.first_point >= CHUNK_POINTS {
+            if style.join == LineJoin::Miter {
+                geometry.update_miter_row(points, i - 1, i + 1, style);
+            }
+
+            let chunk = trail.chunks.last().unwrap();
+            geometry.put_back(meshes.get_mut(&chunk.mesh).unwrap());
+
+            // Miter chunks start with the last row of the previous one, so
+            // the line stays joined
+            let chunk_first_point = match style.join {
+                LineJoin::Miter => i - 1,
+                LineJoin::Round => i,
+            };
+            spawn_chunk(commands, meshes, trail_entity, trail, chunk_first_point);
+
+            geometry = Geometry::take(
+                meshes.get_mut(&trail.chunks.last().unwrap().mesh).unwrap(),
+                chunk_first_point,
+            );
+            if chunk_first_point < i {
+                geometry.push_point(points, colors, chunk_first_point, style);
+            }
+        }
+
+        geometry.push_point(points, colors, i, style);
+    }
+
+    let chunk = trail.chunks.last().unwrap();
+    geometry.put_back(meshes.get_mut(&chunk.mesh).unwrap());
+
+    trail.drawn_points = points.len();
+    trail.style = Some(*style);
+}